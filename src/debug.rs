@@ -0,0 +1,163 @@
+use std::{
+    borrow::Cow,
+    ffi::{c_void, CStr},
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    slice,
+};
+
+use ash::vk;
+
+/// User callback for `VK_EXT_debug_utils` messages.
+pub type DebugCallback = Box<
+    dyn Fn(vk::DebugUtilsMessageSeverityFlagsEXT, vk::DebugUtilsMessageTypeFlagsEXT, &DebugMessage)
+        + Send
+        + Sync
+        + 'static,
+>;
+
+/// A message reported by the driver or a layer through `VK_EXT_debug_utils`.
+pub struct DebugMessage<'a> {
+    pub id_name: Option<Cow<'a, str>>,
+    pub id_number: i32,
+    pub message: Cow<'a, str>,
+    pub queue_labels: Vec<Cow<'a, str>>,
+    pub command_buffer_labels: Vec<Cow<'a, str>>,
+    pub objects: Vec<DebugObject<'a>>,
+}
+
+/// An object referenced by a [`DebugMessage`].
+pub struct DebugObject<'a> {
+    pub object_type: vk::ObjectType,
+    pub handle: u64,
+    pub name: Option<Cow<'a, str>>,
+}
+
+impl<'a> DebugMessage<'a> {
+    unsafe fn from_raw(data: &'a vk::DebugUtilsMessengerCallbackDataEXT) -> Self {
+        let labels = |ptr: *const vk::DebugUtilsLabelEXT, count: u32| {
+            raw_slice(ptr, count)
+                .iter()
+                .filter_map(|label| opt_str(label.p_label_name))
+                .collect()
+        };
+
+        DebugMessage {
+            id_name: opt_str(data.p_message_id_name),
+            id_number: data.message_id_number,
+            message: opt_str(data.p_message).unwrap_or_default(),
+            queue_labels: labels(data.p_queue_labels, data.queue_label_count),
+            command_buffer_labels: labels(data.p_cmd_buf_labels, data.cmd_buf_label_count),
+            objects: raw_slice(data.p_objects, data.object_count)
+                .iter()
+                .map(|object| DebugObject {
+                    object_type: object.object_type,
+                    handle: object.object_handle,
+                    name: opt_str(object.p_object_name),
+                })
+                .collect(),
+        }
+    }
+}
+
+unsafe fn opt_str<'a>(ptr: *const c_char) -> Option<Cow<'a, str>> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(ptr).to_string_lossy())
+    }
+}
+
+unsafe fn raw_slice<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if ptr.is_null() || count == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, count as usize)
+    }
+}
+
+/// Forwards messages to the [`DebugCallback`] passed as `p_user_data`.
+pub(crate) unsafe extern "system" fn debug_callback_trampoline(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_types: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    p_user_data: *mut c_void,
+) -> vk::Bool32 {
+    if p_callback_data.is_null() || p_user_data.is_null() {
+        return vk::FALSE;
+    }
+
+    let callback = &*(p_user_data as *const DebugCallback);
+    let message = DebugMessage::from_raw(&*p_callback_data);
+    // Unwinding into the driver is undefined behavior, so swallow panics here.
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        callback(message_severity, message_types, &message)
+    }));
+
+    vk::FALSE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn trampoline_forwards_messages() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let callback: DebugCallback = Box::new(move |severity, _, message| {
+            sink.lock().unwrap().push((
+                severity,
+                message.id_name.as_deref().map(str::to_owned),
+                message.message.to_string(),
+                message.queue_labels.len(),
+            ));
+        });
+        let user_data = &callback as *const DebugCallback as *mut c_void;
+
+        let data = vk::DebugUtilsMessengerCallbackDataEXT {
+            p_message_id_name: c"VUID-test".as_ptr(),
+            p_message: c"something went wrong".as_ptr(),
+            ..Default::default()
+        };
+        let severity = vk::DebugUtilsMessageSeverityFlagsEXT::ERROR;
+        let types = vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION;
+        unsafe {
+            assert_eq!(
+                debug_callback_trampoline(severity, types, &data, user_data),
+                vk::FALSE
+            );
+            // Missing data is ignored rather than dereferenced.
+            assert_eq!(
+                debug_callback_trampoline(severity, types, std::ptr::null(), user_data),
+                vk::FALSE
+            );
+        }
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            [(
+                severity,
+                Some("VUID-test".to_owned()),
+                "something went wrong".to_owned(),
+                0
+            )]
+        );
+    }
+
+    #[test]
+    fn trampoline_swallows_panics() {
+        let callback: DebugCallback = Box::new(|_, _, _| panic!("callback panicked"));
+        let data = vk::DebugUtilsMessengerCallbackDataEXT::default();
+        let result = unsafe {
+            debug_callback_trampoline(
+                vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL,
+                &data,
+                &callback as *const DebugCallback as *mut c_void,
+            )
+        };
+        assert_eq!(result, vk::FALSE);
+    }
+}
@@ -1,64 +1,249 @@
 use std::{
     any::{Any, TypeId},
+    cmp::Reverse,
     collections::HashMap,
+    ffi::{c_void, CStr, CString},
     os::raw::c_char,
+    ptr,
     sync::Arc,
 };
 
 use ash::vk;
 
-use crate::{util::streq, DeviceExtension, DeviceExtensionLoader, Error, Instance};
+use crate::{
+    extensions::{contains_name, is_promoted, with_core_instance, Dependencies},
+    features::{query_extension_features, ExtensionFeatures, FeatureChain, Features},
+    queue::QueueAllocator,
+    util::{streq, without_patch},
+    DeviceExtension, DeviceExtensionLoader, DeviceReport, Error, ExtensionFeatureStruct, Feature,
+    Instance, PhysicalDeviceInfo, PresentQueuePolicy, PresentTarget, Queue, QueueRequest,
+    QueueRole, Unsuitability,
+};
 
 pub struct Device {
     instance: Arc<Instance>,
     device: ash::Device,
     physical_device: vk::PhysicalDevice,
-    loaded_extensions: HashMap<TypeId, Box<dyn Any + 'static>>,
-    graphics_queue: Option<(u32, vk::Queue)>,
-    compute_queue: Option<(u32, vk::Queue)>,
-    present_queue: Option<(u32, vk::Queue)>,
-    transfer_queue: Option<(u32, vk::Queue)>,
+    info: PhysicalDeviceInfo,
+    api_version: u32,
+    loaded_extensions: HashMap<TypeId, Box<dyn Any + Send + Sync + 'static>>,
+    enabled_features: Features,
+    extension_features: Vec<ExtensionFeatures>,
+    enabled_extensions: Vec<CString>,
+    // Default queues first, then requested ones. Aliases share one Queue.
+    queues: Vec<(QueueRole, Arc<Queue>)>,
+    surface_queues: Vec<(vk::SurfaceKHR, Arc<Queue>)>,
+    present_queue_policy: PresentQueuePolicy,
 }
 
 pub struct DeviceBuilder {
-    required_features: Option<Box<vk::PhysicalDeviceFeatures>>,
-    optional_features: Option<Box<vk::PhysicalDeviceFeatures>>,
+    required_features: Box<Features>,
+    optional_features: Box<Features>,
+    required_extension_features: Vec<ExtensionFeatures>,
+    optional_extension_features: Vec<ExtensionFeatures>,
     required_extensions: Vec<(*const c_char, DeviceExtensionLoader)>,
     optional_extensions: Vec<(*const c_char, DeviceExtensionLoader)>,
-    surface: Option<vk::SurfaceKHR>,
+    surfaces: Vec<vk::SurfaceKHR>,
+    present_target: Option<PresentTarget>,
+    present_queue_policy: PresentQueuePolicy,
     preferred_device: Option<PreferredDevice>,
+    scorer: Option<DeviceScorer>,
+    limits_requirements: Vec<(String, LimitsPredicate)>,
+    properties_requirements: Vec<PropertiesPredicate>,
+    required_formats: Vec<(vk::Format, vk::ImageTiling, vk::FormatFeatureFlags)>,
+    minimum_device_local_memory: vk::DeviceSize,
+    queue_requests: Vec<QueueRequest>,
+    minimum_api_version: u32,
     needs_graphics: bool,
+    compute_only: bool,
+}
+
+/// Ranks suitable physical devices. Returning `None` rejects the device.
+pub type DeviceScorer = Box<dyn Fn(&PhysicalDeviceInfo) -> Option<i64>>;
+
+/// How much of a memory heap the process may use, and how much it uses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeapBudget {
+    pub budget: vk::DeviceSize,
+    pub usage: vk::DeviceSize,
 }
 
+type LimitsPredicate = Box<dyn Fn(&vk::PhysicalDeviceLimits) -> bool>;
+type PropertiesPredicate = Box<dyn Fn(&PhysicalDeviceInfo) -> bool>;
+type RequestedQueue = (QueueRole, u32, u32, bool);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PreferredDevice {
+    /// The device at this index in `vkEnumeratePhysicalDevices`, which can
+    /// change between runs.
     Chosen(u32),
+    /// The device with this [UUID](PhysicalDeviceInfo::device_uuid), to keep
+    /// a choice across runs.
+    Uuid([u8; vk::UUID_SIZE]),
     Discrete,
     Integrated,
+    Virtual,
+    Cpu,
+}
+
+/// The device's UUID, or its index before Vulkan 1.1.
+impl From<&PhysicalDeviceInfo> for PreferredDevice {
+    fn from(info: &PhysicalDeviceInfo) -> Self {
+        let uuid = info.device_uuid();
+        if uuid == [0; vk::UUID_SIZE] {
+            PreferredDevice::Chosen(info.index())
+        } else {
+            PreferredDevice::Uuid(uuid)
+        }
+    }
 }
 
 impl DeviceBuilder {
     pub fn new() -> Self {
         DeviceBuilder {
-            required_features: None,
-            optional_features: None,
+            required_features: Box::default(),
+            optional_features: Box::default(),
+            required_extension_features: Vec::new(),
+            optional_extension_features: Vec::new(),
             required_extensions: Vec::new(),
             optional_extensions: Vec::new(),
-            surface: None,
+            surfaces: Vec::new(),
+            present_target: None,
+            present_queue_policy: PresentQueuePolicy::default(),
             preferred_device: None,
+            scorer: None,
+            limits_requirements: Vec::new(),
+            properties_requirements: Vec::new(),
+            required_formats: Vec::new(),
+            minimum_device_local_memory: 0,
+            queue_requests: Vec::new(),
+            minimum_api_version: vk::API_VERSION_1_0,
             needs_graphics: true,
+            compute_only: false,
         }
     }
 
+    /// Require the features set in `features`, on top of those already required.
     pub fn require_features(mut self, features: vk::PhysicalDeviceFeatures) -> Self {
-        self.required_features = Some(Box::new(features));
+        *self.required_features = self.required_features.union(&Features {
+            vulkan10: features,
+            ..Default::default()
+        });
         self
     }
 
     pub fn optional_features(mut self, features: vk::PhysicalDeviceFeatures) -> Self {
-        self.optional_features = Some(Box::new(features));
+        *self.optional_features = self.optional_features.union(&Features {
+            vulkan10: features,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Require Vulkan 1.1 features. Only devices supporting Vulkan 1.2 can
+    /// report these, so others are rejected.
+    pub fn require_vulkan11_features(
+        mut self,
+        features: vk::PhysicalDeviceVulkan11Features,
+    ) -> Self {
+        *self.required_features = self.required_features.union(&Features {
+            vulkan11: features,
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn optional_vulkan11_features(
+        mut self,
+        features: vk::PhysicalDeviceVulkan11Features,
+    ) -> Self {
+        *self.optional_features = self.optional_features.union(&Features {
+            vulkan11: features,
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn require_vulkan12_features(
+        mut self,
+        features: vk::PhysicalDeviceVulkan12Features,
+    ) -> Self {
+        *self.required_features = self.required_features.union(&Features {
+            vulkan12: features,
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn optional_vulkan12_features(
+        mut self,
+        features: vk::PhysicalDeviceVulkan12Features,
+    ) -> Self {
+        *self.optional_features = self.optional_features.union(&Features {
+            vulkan12: features,
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn require_vulkan13_features(
+        mut self,
+        features: vk::PhysicalDeviceVulkan13Features,
+    ) -> Self {
+        *self.required_features = self.required_features.union(&Features {
+            vulkan13: features,
+            ..Default::default()
+        });
         self
     }
 
+    pub fn optional_vulkan13_features(
+        mut self,
+        features: vk::PhysicalDeviceVulkan13Features,
+    ) -> Self {
+        *self.optional_features = self.optional_features.union(&Features {
+            vulkan13: features,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Require a feature by name, see [`Feature`].
+    pub fn require_feature(mut self, feature: Feature) -> Self {
+        self.required_features.set(feature, true);
+        self
+    }
+
+    pub fn optional_feature(mut self, feature: Feature) -> Self {
+        self.optional_features.set(feature, true);
+        self
+    }
+
+    /// Require the features set in an extension's feature struct, such as
+    /// `vk::PhysicalDeviceMeshShaderFeaturesEXT`. The extension itself must be
+    /// required too. Structs promoted to core, such as
+    /// `vk::PhysicalDeviceTimelineSemaphoreFeatures`, are enabled through the
+    /// per-version structs on devices where those are used.
+    pub fn require_extension_features<T: ExtensionFeatureStruct>(mut self, features: T) -> Self {
+        let features = ExtensionFeatures::new(&features);
+        self.required_extension_features
+            .retain(|f| f.struct_id() != features.struct_id());
+        self.required_extension_features.push(features);
+        self
+    }
+
+    pub fn optional_extension_features<T: ExtensionFeatureStruct>(mut self, features: T) -> Self {
+        let features = ExtensionFeatures::new(&features);
+        self.optional_extension_features
+            .retain(|f| f.struct_id() != features.struct_id());
+        self.optional_extension_features.push(features);
+        self
+    }
+
+    /// Require a device extension and the device extensions it depends on.
+    /// Instance extensions it depends on can't be enabled from here; enable
+    /// them with [`InstanceBuilder::enable_dependencies_of`](crate::InstanceBuilder::enable_dependencies_of),
+    /// otherwise devices are rejected with [`Unsuitability::MissingInstanceExtension`].
     pub fn require_extension<E: DeviceExtension + 'static>(mut self) -> Self {
         if !self
             .required_extensions
@@ -87,9 +272,122 @@ impl DeviceBuilder {
         self
     }
 
+    /// Enable `VK_EXT_memory_budget` if available, for [`Device::memory_budget`].
+    /// On a Vulkan 1.0 instance it also needs
+    /// `InstanceBuilder::enable_dependencies_of::<vk::ExtMemoryBudgetFn>()`.
+    pub fn track_memory_budget(self) -> Self {
+        self.optional_extension::<vk::ExtMemoryBudgetFn>()
+    }
+
+    /// Require support for presenting to the surface, and `VK_KHR_swapchain`.
+    /// Call once per window; each surface gets a
+    /// [present queue](Device::present_queue_for).
     pub fn surface(mut self, surface: vk::SurfaceKHR) -> Self {
-        self.surface = Some(surface);
-        self.require_extension::<ash::extensions::khr::Swapchain>()
+        if !self.surfaces.contains(&surface) {
+            self.surfaces.push(surface);
+        }
+        self
+    }
+
+    /// Require support for presenting to windows on the display before any
+    /// surface exists, and `VK_KHR_swapchain`. Queues for surfaces created later are found with
+    /// [`Device::present_queue_for`].
+    ///
+    /// # Safety
+    /// The display connection in `target` must stay valid until [`build`](Self::build)
+    /// or [`evaluate`](Self::evaluate) returns.
+    pub unsafe fn will_present(mut self, target: PresentTarget) -> Self {
+        self.present_target = Some(target);
+        self
+    }
+
+    /// Choose which family presents when several can.
+    pub fn present_queue_policy(mut self, policy: PresentQueuePolicy) -> Self {
+        self.present_queue_policy = policy;
+        self
+    }
+
+    /// Prefer a device over others that are also suitable. Falls back to the
+    /// other suitable devices if the preferred one isn't.
+    pub fn prefer_device(mut self, preference: PreferredDevice) -> Self {
+        self.preferred_device = Some(preference);
+        self
+    }
+
+    /// Rank suitable devices, picking the highest score. Ties go by device
+    /// type (discrete, integrated, virtual, then CPU), then to the device with
+    /// the most device-local memory, then the one enumerated first. The
+    /// [preference](Self::prefer_device) outranks any score.
+    pub fn score_devices<F>(mut self, scorer: F) -> Self
+    where
+        F: Fn(&PhysicalDeviceInfo) -> Option<i64> + 'static,
+    {
+        self.scorer = Some(Box::new(scorer));
+        self
+    }
+
+    /// Reject devices whose limits don't satisfy the predicate, such as
+    /// `|limits| limits.max_push_constants_size >= 256`. May be called more
+    /// than once; every predicate has to pass. `description` names the
+    /// requirement in [`Unsuitability::LimitsNotMet`] when it fails.
+    pub fn require_limits<F>(mut self, description: impl Into<String>, predicate: F) -> Self
+    where
+        F: Fn(&vk::PhysicalDeviceLimits) -> bool + 'static,
+    {
+        self.limits_requirements
+            .push((description.into(), Box::new(predicate)));
+        self
+    }
+
+    /// Reject devices whose properties don't satisfy the predicate, for
+    /// checks beyond the limits like
+    /// `|info| info.subgroup_properties().subgroup_size == 32`.
+    pub fn require_properties<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&PhysicalDeviceInfo) -> bool + 'static,
+    {
+        self.properties_requirements.push(Box::new(predicate));
+        self
+    }
+
+    /// Reject devices that don't support the format features with the given tiling.
+    pub fn require_format(
+        mut self,
+        format: vk::Format,
+        tiling: vk::ImageTiling,
+        features: vk::FormatFeatureFlags,
+    ) -> Self {
+        self.required_formats.push((format, tiling, features));
+        self
+    }
+
+    /// Reject devices whose device-local heaps add up to less than this many bytes.
+    pub fn minimum_device_local_memory(mut self, bytes: vk::DeviceSize) -> Self {
+        self.minimum_device_local_memory = bytes;
+        self
+    }
+
+    /// Reject devices that can't provide at least this API version. The
+    /// instance's API version counts too, since it caps what a device may use.
+    pub fn minimum_api_version(mut self, version: u32) -> Self {
+        self.minimum_api_version = without_patch(version);
+        self
+    }
+
+    /// Create more queues, to be retrieved with [`Device::queues`]. Devices
+    /// whose family has fewer queues than the request asks for are
+    /// unsuitable; once several requests use up a family, they share the
+    /// queues already created. Priorities must be within [0, 1], and
+    /// [`QueueRole::Present`] requests need a [surface](Self::surface) or
+    /// [present target](Self::will_present). Protected queues also require the
+    /// `protected_memory` feature.
+    pub fn request_queues(mut self, request: QueueRequest) -> Self {
+        if request.is_protected() {
+            self.required_features
+                .set("protected_memory".parse().unwrap(), true);
+        }
+        self.queue_requests.push(request);
+        self
     }
 
     pub fn graphics_optional(mut self) -> Self {
@@ -97,25 +395,59 @@ impl DeviceBuilder {
         self
     }
 
-    pub fn build(self, instance: Arc<Instance>) -> Result<Arc<Device>, Error> {
+    /// Require a compute queue and nothing for graphics or presentation, for
+    /// headless GPGPU work. A dedicated compute family is preferred. Surfaces
+    /// and the present target are ignored, whether given before or after.
+    pub fn compute_only(mut self) -> Self {
+        self.needs_graphics = false;
+        self.compute_only = true;
+        self
+    }
+
+    pub fn build(mut self, instance: Arc<Instance>) -> Result<Arc<Device>, Error> {
+        if self.presents() {
+            self = self.require_extension::<ash::extensions::khr::Swapchain>();
+        }
         unsafe {
-            let physical_devices = instance.instance().enumerate_physical_devices()?;
-            let physical_device = self.select_physical_device(&instance, &physical_devices)?;
-
-            // Enable requested features if available.
-            let mut enabled_features =
-                if self.required_features.is_some() || self.optional_features.is_some() {
-                    instance
-                        .instance()
-                        .get_physical_device_features(physical_device)
-                } else {
-                    Default::default()
-                };
-            if let Some(required_features) = self.required_features {
-                enable_optional_features(&mut enabled_features, &required_features);
-            }
-            if let Some(optional_features) = self.optional_features {
-                enable_optional_features(&mut enabled_features, &optional_features);
+            let reports = self.evaluate(&instance)?;
+            let (index, physical_device) = match self.select_physical_device(&reports) {
+                Some(report) => (report.index, report.physical_device),
+                None => return Err(Error::NoSuitableDevices(reports)),
+            };
+            let info = PhysicalDeviceInfo::query(&instance, index, physical_device)?;
+            let api_version = effective_api_version(&instance, physical_device);
+
+            // Enable requested features if available. Promoted extension
+            // structs are requested through the per-version structs instead.
+            let mut enabled_features = self
+                .required_features
+                .union(&self.optional_features)
+                .union(&promoted_features(
+                    &self.required_extension_features,
+                    api_version,
+                ))
+                .union(&promoted_features(
+                    &self.optional_extension_features,
+                    api_version,
+                ))
+                .intersection(info.all_features());
+
+            let mut available_extension_features = self.extension_feature_structs();
+            query_extension_features(&instance, &info, &mut available_extension_features);
+            let mut extension_features: Vec<ExtensionFeatures> = available_extension_features
+                .iter()
+                .map(ExtensionFeatures::none)
+                .collect();
+            for requested in self
+                .required_extension_features
+                .iter()
+                .chain(&self.optional_extension_features)
+            {
+                let i = available_extension_features
+                    .iter()
+                    .position(|f| f.struct_id() == requested.struct_id())
+                    .unwrap();
+                extension_features[i].enable(&available_extension_features[i], requested);
             }
 
             let mut requested_extensions: Vec<*const c_char> = Vec::new();
@@ -127,191 +459,554 @@ impl DeviceBuilder {
                     .instance()
                     .enumerate_device_extension_properties(physical_device)?;
                 for (name, _) in &self.optional_extensions {
-                    for extension in &extensions {
-                        if streq(*name, extension.extension_name.as_ptr()) {
-                            requested_extensions.push(*name);
-                            break;
+                    if is_promoted(*name, api_version) {
+                        continue;
+                    }
+                    if extension_support_failures(&instance, &extensions, *name, api_version)
+                        .is_empty()
+                    {
+                        requested_extensions.push(*name);
+                        for dep in
+                            Dependencies::of(*name, api_version, instance.api_version()).device
+                        {
+                            if !contains_name(&requested_extensions, dep) {
+                                requested_extensions.push(dep);
+                            }
                         }
                     }
                 }
             }
+            // Extensions that are part of core don't need to be enabled.
             for (name, _) in &self.required_extensions {
-                requested_extensions.push(*name);
+                if is_promoted(*name, api_version) {
+                    continue;
+                }
+                if !contains_name(&requested_extensions, *name) {
+                    requested_extensions.push(*name);
+                }
+                for dep in Dependencies::of(*name, api_version, instance.api_version()).device {
+                    if !contains_name(&requested_extensions, dep) {
+                        requested_extensions.push(dep);
+                    }
+                }
             }
 
             let queue_families = instance
                 .instance()
                 .get_physical_device_queue_family_properties(physical_device);
-            let graphics_queue = DeviceBuilder::find_graphics_queue(&queue_families);
-            let compute_queue =
-                DeviceBuilder::find_compute_queue(&queue_families).or(graphics_queue);
-            let present_queue = self.surface.and_then(|surface| {
-                DeviceBuilder::find_present_queue(
+            let graphics_queue = if self.compute_only {
+                None
+            } else {
+                DeviceBuilder::find_graphics_queue(&queue_families)
+            };
+            let compute_queue = DeviceBuilder::find_compute_queue(&queue_families);
+            let mut present_queues = Vec::new();
+            for &surface in self.surfaces() {
+                if let Some(family) = DeviceBuilder::find_present_queue(
                     &instance,
                     physical_device,
                     surface,
                     &queue_families,
+                    self.present_queue_policy,
+                )? {
+                    present_queues.push((surface, family));
+                }
+            }
+            let target_present_queue = self.present_target().and_then(|target| {
+                DeviceBuilder::find_target_present_queue(
+                    &instance,
+                    physical_device,
+                    target,
+                    &queue_families,
+                    self.present_queue_policy,
                 )
-                .unwrap_or_default()
             });
             let transfer_queue = DeviceBuilder::find_transfer_queue(&queue_families);
 
-            let mut queue_families = Vec::<u32>::new();
-            for qf in [graphics_queue, compute_queue, present_queue, transfer_queue]
-                .into_iter()
-                .flatten()
-            {
-                if !queue_families.contains(&qf) {
-                    queue_families.push(qf)
-                }
+            let mut request_families = Vec::new();
+            for request in &self.queue_requests {
+                request_families.push(
+                    self.find_requested_queue(
+                        &instance,
+                        physical_device,
+                        &queue_families,
+                        request,
+                    )?
+                    .unwrap(),
+                );
             }
-            let queue_create_infos: Vec<vk::DeviceQueueCreateInfo> = queue_families
-                .into_iter()
-                .map(|qf| {
-                    vk::DeviceQueueCreateInfo::builder()
-                        .queue_family_index(qf)
-                        .queue_priorities(&[1.0f32])
-                        .build()
-                })
-                .collect();
+            // The device was evaluated, so every requested queue gets allocated.
+            let (allocator, requested_queues) = self
+                .allocate_queues(
+                    &queue_families,
+                    [graphics_queue, compute_queue, transfer_queue]
+                        .into_iter()
+                        .flatten()
+                        .chain(present_queues.iter().map(|(_, family)| *family))
+                        .chain(target_present_queue),
+                    &request_families,
+                )
+                .unwrap();
+            let queue_create_infos = allocator.create_infos();
 
-            let create_info = vk::DeviceCreateInfo::builder()
+            // Structs of extensions that weren't enabled may not be chained.
+            let is_enabled = |name: &CStr| contains_name(&requested_extensions, name.as_ptr());
+            for features in &mut extension_features {
+                if !features.is_known(api_version, is_enabled) {
+                    *features = features.none();
+                }
+            }
+            let mut extension_feature_chain =
+                device_extension_feature_chain(&extension_features, api_version, is_enabled);
+            let p_next = device_feature_chain(
+                &mut enabled_features,
+                &mut extension_feature_chain,
+                api_version,
+            );
+            let mut create_info = vk::DeviceCreateInfo::builder()
                 .enabled_extension_names(&requested_extensions)
-                .enabled_features(&enabled_features)
+                .enabled_features(&enabled_features.vulkan10)
                 .queue_create_infos(&queue_create_infos);
+            create_info.p_next = p_next;
             let device = instance
                 .instance()
                 .create_device(physical_device, &create_info, None)?;
+            enabled_features.vulkan11.p_next = ptr::null_mut();
+            enabled_features.vulkan12.p_next = ptr::null_mut();
+            enabled_features.vulkan13.p_next = ptr::null_mut();
 
-            let mut loaded_extensions: HashMap<TypeId, Box<dyn Any + 'static>> = HashMap::new();
-            for (name, loader) in self.optional_extensions {
-                if requested_extensions.contains(&name) {
-                    let ext = loader(instance.instance(), &device);
+            let mut loaded_extensions: HashMap<TypeId, Box<dyn Any + Send + Sync + 'static>> =
+                HashMap::new();
+            with_core_instance(instance.entry(), instance.instance(), |core_instance| {
+                let loader_instance = |name: *const c_char| {
+                    if requested_extensions.contains(&name) {
+                        instance.instance()
+                    } else {
+                        core_instance
+                    }
+                };
+                for (name, loader) in self.optional_extensions {
+                    if requested_extensions.contains(&name) || is_promoted(name, api_version) {
+                        let ext = loader(loader_instance(name), &device);
+                        let id = ext.as_ref().type_id();
+                        loaded_extensions.insert(id, ext);
+                    }
+                }
+                for (name, loader) in self.required_extensions {
+                    let ext = loader(loader_instance(name), &device);
                     let id = ext.as_ref().type_id();
                     loaded_extensions.insert(id, ext);
                 }
+            });
+
+            let mut present_families = Vec::new();
+            for family in present_queues
+                .iter()
+                .map(|(_, family)| *family)
+                .chain(target_present_queue)
+            {
+                if !present_families.contains(&family) {
+                    present_families.push(family);
+                }
             }
-            for (_, loader) in self.required_extensions {
-                let ext = loader(instance.instance(), &device);
-                let id = ext.as_ref().type_id();
-                loaded_extensions.insert(id, ext);
+            let default_queues = [
+                (QueueRole::Graphics, graphics_queue),
+                (QueueRole::Compute, compute_queue),
+                (QueueRole::Transfer, transfer_queue),
+            ]
+            .into_iter()
+            .chain(
+                present_families
+                    .into_iter()
+                    .map(|family| (QueueRole::Present, Some(family))),
+            )
+            .filter_map(|(role, family)| Some((role, family?, 0, false)));
+            let mut created: Vec<((u32, u32, bool), Arc<Queue>)> = Vec::new();
+            let mut queues = Vec::new();
+            for (role, family, index, protected) in default_queues.chain(requested_queues) {
+                let key = (family, index, protected);
+                let queue = match created.iter().find(|(k, _)| *k == key) {
+                    Some((_, queue)) => queue.clone(),
+                    None => {
+                        let handle = if protected {
+                            let queue_info = vk::DeviceQueueInfo2::builder()
+                                .flags(vk::DeviceQueueCreateFlags::PROTECTED)
+                                .queue_family_index(family)
+                                .queue_index(index);
+                            device.get_device_queue2(&queue_info)
+                        } else {
+                            device.get_device_queue(family, index)
+                        };
+                        let queue = Arc::new(Queue::new(
+                            device.clone(),
+                            handle,
+                            family,
+                            index,
+                            &queue_families[family as usize],
+                        ));
+                        created.push((key, queue.clone()));
+                        queue
+                    }
+                };
+                queues.push((role, queue));
             }
-
-            let graphics_queue = graphics_queue.map(|qf| (qf, device.get_device_queue(qf, 0)));
-            let compute_queue = compute_queue.map(|qf| (qf, device.get_device_queue(qf, 0)));
-            let present_queue = present_queue.map(|qf| (qf, device.get_device_queue(qf, 0)));
-            let transfer_queue = transfer_queue.map(|qf| (qf, device.get_device_queue(qf, 0)));
+            let surface_queues = present_queues
+                .into_iter()
+                .map(|(surface, family)| {
+                    let (_, queue) = created
+                        .iter()
+                        .find(|(key, _)| *key == (family, 0, false))
+                        .unwrap();
+                    (surface, queue.clone())
+                })
+                .collect();
 
             Ok(Arc::new(Device {
                 instance,
                 device,
                 physical_device,
+                info,
+                api_version,
                 loaded_extensions,
-                graphics_queue,
-                compute_queue,
-                present_queue,
-                transfer_queue,
+                enabled_features,
+                extension_features,
+                enabled_extensions: requested_extensions
+                    .iter()
+                    .map(|name| CStr::from_ptr(*name).to_owned())
+                    .collect(),
+                queues,
+                surface_queues,
+                present_queue_policy: self.present_queue_policy,
             }))
         }
     }
 
-    unsafe fn select_physical_device(
+    /// Check every physical device against the builder's requirements.
+    pub fn evaluate(&self, instance: &Instance) -> Result<Vec<DeviceReport>, Error> {
+        for request in &self.queue_requests {
+            request.check_priorities()?;
+            if request.role == QueueRole::Present
+                && self.surfaces().is_empty()
+                && self.present_target().is_none()
+            {
+                return Err(Error::NothingToPresentTo);
+            }
+        }
+        unsafe {
+            instance
+                .physical_devices()?
+                .iter()
+                .map(|info| self.evaluate_device(instance, info))
+                .collect()
+        }
+    }
+
+    /// Pick the suitable device matching the preference, then the one with the
+    /// highest score, then the one with the most device-local memory, then the
+    /// first one enumerated.
+    fn select_physical_device<'a>(&self, reports: &'a [DeviceReport]) -> Option<&'a DeviceReport> {
+        let is_preferred = |report: &DeviceReport| match self.preferred_device {
+            Some(PreferredDevice::Chosen(idx)) => report.index == idx,
+            Some(PreferredDevice::Uuid(uuid)) => report.device_uuid == uuid,
+            Some(PreferredDevice::Discrete) => {
+                report.device_type == vk::PhysicalDeviceType::DISCRETE_GPU
+            }
+            Some(PreferredDevice::Integrated) => {
+                report.device_type == vk::PhysicalDeviceType::INTEGRATED_GPU
+            }
+            Some(PreferredDevice::Virtual) => {
+                report.device_type == vk::PhysicalDeviceType::VIRTUAL_GPU
+            }
+            Some(PreferredDevice::Cpu) => report.device_type == vk::PhysicalDeviceType::CPU,
+            None => false,
+        };
+
+        reports
+            .iter()
+            .filter(|report| report.is_suitable())
+            .max_by_key(|report| {
+                (
+                    is_preferred(report),
+                    report.score,
+                    device_type_rank(report.device_type),
+                    report.device_local_memory,
+                    Reverse(report.index),
+                )
+            })
+    }
+
+    unsafe fn evaluate_device(
         &self,
         instance: &Instance,
-        physical_devices: &[vk::PhysicalDevice],
-    ) -> Result<vk::PhysicalDevice, Error> {
-        if let Some(preferred_device) = &self.preferred_device {
-            match preferred_device {
-                PreferredDevice::Chosen(idx) => {
-                    let idx = *idx as usize;
-                    if idx < physical_devices.len()
-                        && self.is_device_suitable(instance, physical_devices[idx])?
-                    {
-                        return Ok(physical_devices[idx]);
-                    }
-                }
-                PreferredDevice::Discrete => {
-                    for &pd in physical_devices {
-                        let props = instance.instance().get_physical_device_properties(pd);
-                        if props.device_type == vk::PhysicalDeviceType::DISCRETE_GPU
-                            && self.is_device_suitable(instance, pd)?
-                        {
-                            return Ok(pd);
-                        }
+        info: &PhysicalDeviceInfo,
+    ) -> Result<DeviceReport, Error> {
+        let device = info.physical_device();
+        let mut failures = Vec::new();
+
+        let api_version = effective_api_version(instance, device);
+        if api_version < self.minimum_api_version {
+            failures.push(Unsuitability::ApiVersionTooLow {
+                required: self.minimum_api_version,
+                available: api_version,
+            });
+        }
+
+        let mut missing: Vec<String> = self
+            .required_features
+            .difference(info.all_features())
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        if !self.required_extension_features.is_empty() {
+            let mut available = self.extension_feature_structs();
+            query_extension_features(instance, info, &mut available);
+            for required in &self.required_extension_features {
+                let available = available
+                    .iter()
+                    .find(|f| f.struct_id() == required.struct_id())
+                    .unwrap();
+                missing.extend(required.missing(available));
+            }
+        }
+        if !missing.is_empty() {
+            failures.push(Unsuitability::MissingFeatures(missing));
+        }
+
+        for req in self.required_extension_names() {
+            if !is_promoted(req, api_version) {
+                for failure in
+                    extension_support_failures(instance, info.extensions(), req, api_version)
+                {
+                    if !failures.contains(&failure) {
+                        failures.push(failure);
                     }
                 }
-                PreferredDevice::Integrated => {
-                    for &pd in physical_devices {
-                        let props = instance.instance().get_physical_device_properties(pd);
-                        if props.device_type == vk::PhysicalDeviceType::DISCRETE_GPU
-                            && self.is_device_suitable(instance, pd)?
-                        {
-                            return Ok(pd);
-                        }
+            }
+        }
+
+        let unmet_limits = self.unmet_limits(info.limits());
+        if !unmet_limits.is_empty() {
+            failures.push(Unsuitability::LimitsNotMet(unmet_limits));
+        }
+        if !self
+            .properties_requirements
+            .iter()
+            .all(|predicate| predicate(info))
+        {
+            failures.push(Unsuitability::PropertiesNotMet);
+        }
+
+        for &(format, tiling, features) in &self.required_formats {
+            if !supports_format(instance, device, format, tiling, features) {
+                failures.push(Unsuitability::FormatUnsupported {
+                    format,
+                    tiling,
+                    features,
+                });
+            }
+        }
+
+        let mut request_families = Vec::new();
+        for request in &self.queue_requests {
+            let family =
+                self.find_requested_queue(instance, device, info.queue_families(), request)?;
+            request_families.extend(family);
+            let failure = match family {
+                None => Unsuitability::NoQueueFamily(request.flags),
+                Some(family)
+                    if request.priorities.len()
+                        > info.queue_families()[family as usize].queue_count as usize =>
+                {
+                    Unsuitability::NotEnoughQueues {
+                        flags: request.flags,
+                        requested: request.priorities.len(),
+                        available: info.queue_families()[family as usize].queue_count,
                     }
                 }
+                Some(_) => continue,
+            };
+            if !failures.contains(&failure) {
+                failures.push(failure);
             }
         }
 
-        // If there's no preference, just select the first suitable device.
-        for &pd in physical_devices {
-            if self.is_device_suitable(instance, pd)? {
-                return Ok(pd);
+        let device_local_memory = info.device_local_memory();
+        if device_local_memory < self.minimum_device_local_memory {
+            failures.push(Unsuitability::NotEnoughMemory {
+                required: self.minimum_device_local_memory,
+                available: device_local_memory,
+            });
+        }
+
+        let queue_families = info.queue_families();
+
+        if self.needs_graphics && DeviceBuilder::find_graphics_queue(queue_families).is_none() {
+            failures.push(Unsuitability::NoGraphicsQueue);
+        }
+
+        if self.compute_only && DeviceBuilder::find_compute_queue(queue_families).is_none() {
+            failures.push(Unsuitability::NoComputeQueue);
+        }
+
+        let mut present_families = Vec::new();
+        for &surface in self.surfaces() {
+            match DeviceBuilder::find_present_queue(
+                instance,
+                device,
+                surface,
+                queue_families,
+                self.present_queue_policy,
+            )? {
+                Some(family) => present_families.push(family),
+                None => failures.push(Unsuitability::NoPresentSupport(surface)),
+            }
+        }
+
+        if let Some(target) = self.present_target() {
+            match DeviceBuilder::find_target_present_queue(
+                instance,
+                device,
+                target,
+                queue_families,
+                self.present_queue_policy,
+            ) {
+                Some(family) => present_families.push(family),
+                None => failures.push(Unsuitability::NoPresentTargetSupport),
             }
         }
 
-        Err(Error::NoSuitableDevices)
+        if request_families.len() == self.queue_requests.len() {
+            let graphics_queue = if self.compute_only {
+                None
+            } else {
+                DeviceBuilder::find_graphics_queue(queue_families)
+            };
+            if let Err(failure) = self.allocate_queues(
+                queue_families,
+                [
+                    graphics_queue,
+                    DeviceBuilder::find_compute_queue(queue_families),
+                    DeviceBuilder::find_transfer_queue(queue_families),
+                ]
+                .into_iter()
+                .flatten()
+                .chain(present_families),
+                &request_families,
+            ) {
+                failures.push(failure);
+            }
+        }
+
+        let score = match &self.scorer {
+            Some(scorer) => scorer(info),
+            None => Some(0),
+        };
+        if score.is_none() {
+            failures.push(Unsuitability::RejectedByScorer);
+        }
+
+        Ok(DeviceReport {
+            physical_device: device,
+            index: info.index(),
+            name: info.name().into_owned(),
+            device_uuid: info.device_uuid(),
+            device_type: info.device_type(),
+            score: score.unwrap_or_default(),
+            device_local_memory,
+            failures,
+        })
     }
 
-    unsafe fn is_device_suitable(
+    /// Allocate the first queue of each default family, which the default
+    /// queues share, then the requested queues in `request_families`. Fails
+    /// when a request's family has no queue left for its protection.
+    fn allocate_queues(
         &self,
-        instance: &Instance,
-        device: vk::PhysicalDevice,
-    ) -> Result<bool, Error> {
-        if let Some(required) = &self.required_features {
-            let available_features = instance.instance().get_physical_device_features(device);
-
-            if !has_required_features(&available_features, required.as_ref()) {
-                return Ok(false);
+        queue_families: &[vk::QueueFamilyProperties],
+        default_families: impl IntoIterator<Item = u32>,
+        request_families: &[u32],
+    ) -> Result<(QueueAllocator, Vec<RequestedQueue>), Unsuitability> {
+        let mut allocator = QueueAllocator::new(queue_families);
+        let mut allocated_families = Vec::new();
+        for family in default_families {
+            if !allocated_families.contains(&family) {
+                allocator.allocate(family, false, 1.0);
+                allocated_families.push(family);
             }
         }
-
-        if !self.required_extensions.is_empty() {
-            let available_extensions = instance
-                .instance()
-                .enumerate_device_extension_properties(device)?;
-
-            for (req, _) in &self.required_extensions {
-                let mut found = false;
-                for ext in &available_extensions {
-                    if streq(ext.extension_name.as_ptr(), *req) {
-                        found = true;
-                        break;
-                    }
-                }
-                if !found {
-                    return Ok(false);
-                }
+        let mut requested_queues = Vec::new();
+        for (request, &family) in self.queue_requests.iter().zip(request_families) {
+            let protected = request.is_protected();
+            for &priority in &request.priorities {
+                let index = allocator.allocate(family, protected, priority).ok_or(
+                    Unsuitability::QueuesTaken {
+                        flags: request.flags,
+                        protected,
+                    },
+                )?;
+                requested_queues.push((request.role, family, index, protected));
             }
         }
+        Ok((allocator, requested_queues))
+    }
 
-        let queue_families = instance
-            .instance()
-            .get_physical_device_queue_family_properties(device);
+    /// The descriptions of the limits requirements that fail.
+    fn unmet_limits(&self, limits: &vk::PhysicalDeviceLimits) -> Vec<String> {
+        self.limits_requirements
+            .iter()
+            .filter(|(_, predicate)| !predicate(limits))
+            .map(|(description, _)| description.clone())
+            .collect()
+    }
 
-        if self.needs_graphics && DeviceBuilder::find_graphics_queue(&queue_families).is_none() {
-            return Ok(false);
+    /// Compute-only devices ignore surfaces and the present target, whichever
+    /// order they were given in.
+    fn presents(&self) -> bool {
+        !self.surfaces().is_empty() || self.present_target().is_some()
+    }
+
+    fn surfaces(&self) -> &[vk::SurfaceKHR] {
+        if self.compute_only {
+            &[]
+        } else {
+            &self.surfaces
         }
+    }
+
+    fn present_target(&self) -> Option<PresentTarget> {
+        self.present_target.filter(|_| !self.compute_only)
+    }
 
-        if let Some(surface) = self.surface {
-            if DeviceBuilder::find_present_queue(instance, device, surface, &queue_families)?
-                .is_none()
+    /// The required extensions, with the swapchain extension when presenting.
+    fn required_extension_names(&self) -> Vec<*const c_char> {
+        let mut names: Vec<*const c_char> = self
+            .required_extensions
+            .iter()
+            .map(|(name, _)| *name)
+            .collect();
+        let swapchain = <ash::extensions::khr::Swapchain as DeviceExtension>::name();
+        if self.presents() && !names.contains(&swapchain) {
+            names.push(swapchain);
+        }
+        names
+    }
+
+    /// One empty struct of each extension feature type that was asked for.
+    fn extension_feature_structs(&self) -> Vec<ExtensionFeatures> {
+        let mut structs: Vec<ExtensionFeatures> = Vec::new();
+        for features in self
+            .required_extension_features
+            .iter()
+            .chain(&self.optional_extension_features)
+        {
+            if !structs
+                .iter()
+                .any(|f| f.struct_id() == features.struct_id())
             {
-                return Ok(false);
+                structs.push(features.none());
             }
         }
-
-        Ok(true)
+        structs
     }
 
     fn find_graphics_queue(queue_families: &[vk::QueueFamilyProperties]) -> Option<u32> {
@@ -332,18 +1027,100 @@ impl DeviceBuilder {
         device: vk::PhysicalDevice,
         surface: vk::SurfaceKHR,
         queue_families: &[vk::QueueFamilyProperties],
+        policy: PresentQueuePolicy,
     ) -> Result<Option<u32>, Error> {
         let surface_ext = instance
             .extension::<ash::extensions::khr::Surface>()
             .unwrap();
 
-        for i in 0..queue_families.len() {
-            if surface_ext.get_physical_device_surface_support(device, i as u32, surface)? {
-                return Ok(Some(i as u32));
+        let mut supported = Vec::new();
+        for i in 0..queue_families.len() as u32 {
+            if surface_ext.get_physical_device_surface_support(device, i, surface)? {
+                supported.push(i);
             }
         }
 
-        Ok(None)
+        Ok(DeviceBuilder::pick_present_queue(
+            queue_families,
+            &supported,
+            policy,
+        ))
+    }
+
+    unsafe fn find_target_present_queue(
+        instance: &Instance,
+        device: vk::PhysicalDevice,
+        target: PresentTarget,
+        queue_families: &[vk::QueueFamilyProperties],
+        policy: PresentQueuePolicy,
+    ) -> Option<u32> {
+        let supported: Vec<u32> = (0..queue_families.len() as u32)
+            .filter(|&i| supports_present_target(instance, device, i, target))
+            .collect();
+        DeviceBuilder::pick_present_queue(queue_families, &supported, policy)
+    }
+
+    /// Apply the policy to the families that can present.
+    fn pick_present_queue(
+        queue_families: &[vk::QueueFamilyProperties],
+        supported: &[u32],
+        policy: PresentQueuePolicy,
+    ) -> Option<u32> {
+        let graphics = DeviceBuilder::find_graphics_queue(queue_families);
+        let preferred = match policy {
+            PresentQueuePolicy::PreferGraphics => graphics,
+            PresentQueuePolicy::PreferDedicated => supported
+                .iter()
+                .copied()
+                .find(|family| Some(*family) != graphics),
+            PresentQueuePolicy::PreferCompute => DeviceBuilder::find_compute_queue(queue_families),
+        };
+
+        preferred
+            .filter(|family| supported.contains(family))
+            .or(graphics.filter(|family| supported.contains(family)))
+            .or(supported.first().copied())
+    }
+
+    /// The family supporting the request with the fewest other capabilities.
+    /// Present requests need a family that can present to every surface and
+    /// the present target.
+    unsafe fn find_requested_queue(
+        &self,
+        instance: &Instance,
+        device: vk::PhysicalDevice,
+        queue_families: &[vk::QueueFamilyProperties],
+        request: &QueueRequest,
+    ) -> Result<Option<u32>, Error> {
+        let mut best: Option<(u32, u32)> = None;
+        for (i, qf) in queue_families.iter().enumerate() {
+            if !qf.queue_flags.contains(request.flags) {
+                continue;
+            }
+            if request.role == QueueRole::Present {
+                if self.surfaces().is_empty() && self.present_target().is_none() {
+                    return Ok(None);
+                }
+                let mut presents = self.present_target().is_none_or(|target| {
+                    supports_present_target(instance, device, i as u32, target)
+                });
+                for &surface in self.surfaces() {
+                    let surface_ext = instance
+                        .extension::<ash::extensions::khr::Surface>()
+                        .unwrap();
+                    presents &= surface_ext
+                        .get_physical_device_surface_support(device, i as u32, surface)?;
+                }
+                if !presents {
+                    continue;
+                }
+            }
+            let extra = (qf.queue_flags.as_raw() & !request.flags.as_raw()).count_ones();
+            if best.is_none_or(|(fewest, _)| extra < fewest) {
+                best = Some((extra, i as u32));
+            }
+        }
+        Ok(best.map(|(_, family)| family))
     }
 
     fn find_transfer_queue(queue_families: &[vk::QueueFamilyProperties]) -> Option<u32> {
@@ -360,6 +1137,7 @@ impl DeviceBuilder {
         None
     }
 
+    /// A dedicated compute family if there is one, otherwise any compute family.
     fn find_compute_queue(queue_families: &[vk::QueueFamilyProperties]) -> Option<u32> {
         for (i, qf) in queue_families.iter().enumerate() {
             if qf.queue_flags.contains(vk::QueueFlags::COMPUTE)
@@ -369,7 +1147,10 @@ impl DeviceBuilder {
             }
         }
 
-        None
+        queue_families
+            .iter()
+            .position(|qf| qf.queue_flags.contains(vk::QueueFlags::COMPUTE))
+            .map(|i| i as u32)
     }
 }
 
@@ -392,6 +1173,12 @@ impl Device {
         self.physical_device
     }
 
+    /// The API version usable with this device: the lower of the instance's
+    /// and the physical device's version.
+    pub fn api_version(&self) -> u32 {
+        self.api_version
+    }
+
     pub fn extension<E: DeviceExtension + 'static>(&self) -> Option<&E> {
         let id = TypeId::of::<E>();
         self.loaded_extensions
@@ -399,225 +1186,794 @@ impl Device {
             .map(|e| e.downcast_ref::<E>().unwrap())
     }
 
-    pub fn graphics_queue(&self) -> Option<(u32, vk::Queue)> {
-        self.graphics_queue
+    /// The properties of the physical device, as queried during selection.
+    pub fn physical_device_info(&self) -> &PhysicalDeviceInfo {
+        &self.info
+    }
+
+    pub fn properties(&self) -> &vk::PhysicalDeviceProperties {
+        self.info.properties()
     }
 
-    pub fn compute_queue(&self) -> Option<(u32, vk::Queue)> {
-        self.compute_queue
+    pub fn limits(&self) -> &vk::PhysicalDeviceLimits {
+        self.info.limits()
+    }
+
+    /// The first of the candidates that supports the features with the given
+    /// tiling, such as a depth format out of `D32_SFLOAT_S8_UINT` and
+    /// `D24_UNORM_S8_UINT`.
+    pub fn find_supported_format(
+        &self,
+        candidates: &[vk::Format],
+        tiling: vk::ImageTiling,
+        features: vk::FormatFeatureFlags,
+    ) -> Option<vk::Format> {
+        first_supported_format(candidates, tiling, features, |format| unsafe {
+            self.instance
+                .instance()
+                .get_physical_device_format_properties(self.physical_device, format)
+        })
+    }
+
+    pub fn memory_properties(&self) -> &vk::PhysicalDeviceMemoryProperties {
+        self.info.memory_properties()
+    }
+
+    /// The index of a memory type allowed by `type_bits` that has the
+    /// `required` flags, favoring one that also has the `preferred` flags.
+    pub fn find_memory_type(
+        &self,
+        type_bits: u32,
+        required: vk::MemoryPropertyFlags,
+        preferred: vk::MemoryPropertyFlags,
+    ) -> Option<u32> {
+        find_memory_type(self.memory_properties(), type_bits, required, preferred)
     }
 
-    pub fn present_queue(&self) -> Option<(u32, vk::Queue)> {
-        self.present_queue
+    /// The budget and usage of each memory heap. Without `VK_EXT_memory_budget`
+    /// (see [`DeviceBuilder::track_memory_budget`]) the budget is the heap size
+    /// and the usage is unknown, reported as 0.
+    pub fn memory_budget(&self) -> Vec<HeapBudget> {
+        let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let queried = self.is_extension_enabled(vk::ExtMemoryBudgetFn::name())
+            && unsafe {
+                let mut properties2 =
+                    vk::PhysicalDeviceMemoryProperties2::builder().push_next(&mut budget);
+                self.instance
+                    .get_physical_device_memory_properties2(self.physical_device, &mut properties2)
+            };
+        heap_budgets(self.memory_properties(), queried.then_some(&budget))
     }
 
-    pub fn transfer_queue(&self) -> Option<(u32, vk::Queue)> {
-        self.transfer_queue
+    /// The features that were requested and available.
+    pub fn enabled_features(&self) -> &Features {
+        &self.enabled_features
+    }
+
+    /// The extensions that were enabled. Extensions provided by the API
+    /// version aren't listed.
+    pub fn enabled_extensions(&self) -> &[CString] {
+        &self.enabled_extensions
+    }
+
+    /// Whether the extension was enabled or is provided by the device's API version.
+    pub fn is_extension_enabled(&self, name: &CStr) -> bool {
+        let promoted = unsafe { is_promoted(name.as_ptr(), self.api_version) };
+        promoted || self.enabled_extensions.iter().any(|ext| **ext == *name)
+    }
+
+    /// The members of an extension feature struct that were enabled, if the
+    /// struct was passed to the builder.
+    pub fn extension_features<T: ExtensionFeatureStruct>(&self) -> Option<T> {
+        self.extension_features
+            .iter()
+            .find(|f| f.struct_id() == TypeId::of::<T>())
+            .map(ExtensionFeatures::to_struct)
+    }
+
+    /// The first queue with the graphics role.
+    pub fn graphics_queue(&self) -> Option<&Queue> {
+        self.queues(QueueRole::Graphics).first().copied()
+    }
+
+    pub fn compute_queue(&self) -> Option<&Queue> {
+        self.queues(QueueRole::Compute).first().copied()
+    }
+
+    /// The present queue of the first surface.
+    pub fn present_queue(&self) -> Option<&Queue> {
+        self.queues(QueueRole::Present).first().copied()
+    }
+
+    pub fn transfer_queue(&self) -> Option<&Queue> {
+        self.queues(QueueRole::Transfer).first().copied()
+    }
+
+    /// The queue presenting to the surface. For surfaces not passed to
+    /// [`DeviceBuilder::surface`], such as ones created after the device, this
+    /// is the first of the present, graphics and other queues that can
+    /// present to it.
+    pub fn present_queue_for(&self, surface: vk::SurfaceKHR) -> Result<Option<&Queue>, Error> {
+        if let Some((_, queue)) = self.surface_queues.iter().find(|(s, _)| *s == surface) {
+            return Ok(Some(queue));
+        }
+
+        let Some(surface_ext) = self.instance.extension::<ash::extensions::khr::Surface>() else {
+            return Ok(None);
+        };
+        let candidates = self
+            .queues(QueueRole::Present)
+            .into_iter()
+            .chain(self.queues(QueueRole::Graphics))
+            .chain(self.queues.iter().map(|(_, queue)| queue.as_ref()));
+        for queue in candidates {
+            let supported = unsafe {
+                surface_ext.get_physical_device_surface_support(
+                    self.physical_device,
+                    queue.family_index(),
+                    surface,
+                )?
+            };
+            if supported {
+                return Ok(Some(queue));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The policy the present queues were chosen with. The queue chosen for
+    /// each surface is [`present_queue_for`](Self::present_queue_for).
+    pub fn present_queue_policy(&self) -> PresentQueuePolicy {
+        self.present_queue_policy
+    }
+
+    /// The queue whose family renders presented images: the compute queue
+    /// with [`PresentQueuePolicy::PreferCompute`], the graphics queue otherwise.
+    pub(crate) fn render_queue(&self) -> Option<&Queue> {
+        match self.present_queue_policy() {
+            PresentQueuePolicy::PreferCompute => self.compute_queue(),
+            _ => self.graphics_queue(),
+        }
+    }
+
+    /// The queues with the role: the default queue first, if there is one,
+    /// then those from [`QueueRequest`]s in request order.
+    pub fn queues(&self, role: QueueRole) -> Vec<&Queue> {
+        self.queues
+            .iter()
+            .filter(|(r, _)| *r == role)
+            .map(|(_, queue)| queue.as_ref())
+            .collect()
     }
 }
 
-impl Drop for Device {
-    fn drop(&mut self) {
-        unsafe {
-            self.device.destroy_device(None);
+/// The reasons the extension or one of its dependencies can't be enabled.
+/// Instance extensions it depends on must already be enabled on the instance.
+unsafe fn extension_support_failures(
+    instance: &Instance,
+    available: &[vk::ExtensionProperties],
+    name: *const c_char,
+    api_version: u32,
+) -> Vec<Unsuitability> {
+    let is_available = |name: *const c_char| {
+        available
+            .iter()
+            .any(|ext| streq(ext.extension_name.as_ptr(), name))
+    };
+    let to_string = |name: *const c_char| CStr::from_ptr(name).to_string_lossy().into_owned();
+
+    let deps = Dependencies::of(name, api_version, instance.api_version());
+    let mut failures = Vec::new();
+    for ext in std::iter::once(name).chain(deps.device) {
+        if !is_available(ext) {
+            failures.push(Unsuitability::MissingExtension(to_string(ext)));
         }
     }
+    for ext in deps.instance {
+        if !instance.is_extension_enabled(CStr::from_ptr(ext)) {
+            failures.push(Unsuitability::MissingInstanceExtension {
+                name: to_string(ext),
+                required_by: to_string(name),
+            });
+        }
+    }
+
+    failures
 }
 
-macro_rules! check_required_feature {
-    ($available:ident, $required:ident, $field:ident) => {
-        if $required.$field != 0 && !$available.$field == 0 {
-            return false;
+unsafe fn supports_present_target(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    queue_family: u32,
+    target: PresentTarget,
+) -> bool {
+    use ash::extensions::khr::{WaylandSurface, Win32Surface, XcbSurface, XlibSurface};
+
+    match target {
+        PresentTarget::Win32 => instance.extension::<Win32Surface>().is_some_and(|ext| {
+            ext.get_physical_device_win32_presentation_support(physical_device, queue_family)
+        }),
+        PresentTarget::Wayland { display } => {
+            instance.extension::<WaylandSurface>().is_some_and(|ext| {
+                ext.get_physical_device_wayland_presentation_support(
+                    physical_device,
+                    queue_family,
+                    &mut *display,
+                )
+            })
         }
-    };
+        PresentTarget::Xcb {
+            connection,
+            visual_id,
+        } => instance.extension::<XcbSurface>().is_some_and(|ext| {
+            ext.get_physical_device_xcb_presentation_support(
+                physical_device,
+                queue_family,
+                &mut *connection,
+                visual_id,
+            )
+        }),
+        PresentTarget::Xlib { display, visual_id } => {
+            instance.extension::<XlibSurface>().is_some_and(|ext| {
+                ext.get_physical_device_xlib_presentation_support(
+                    physical_device,
+                    queue_family,
+                    &mut *display,
+                    visual_id,
+                )
+            })
+        }
+    }
 }
 
-fn has_required_features(
-    available: &vk::PhysicalDeviceFeatures,
-    required: &vk::PhysicalDeviceFeatures,
+unsafe fn supports_format(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    format: vk::Format,
+    tiling: vk::ImageTiling,
+    features: vk::FormatFeatureFlags,
 ) -> bool {
-    check_required_feature!(available, required, robust_buffer_access);
-    check_required_feature!(available, required, full_draw_index_uint32);
-    check_required_feature!(available, required, image_cube_array);
-    check_required_feature!(available, required, independent_blend);
-    check_required_feature!(available, required, geometry_shader);
-    check_required_feature!(available, required, tessellation_shader);
-    check_required_feature!(available, required, sample_rate_shading);
-    check_required_feature!(available, required, dual_src_blend);
-    check_required_feature!(available, required, logic_op);
-    check_required_feature!(available, required, multi_draw_indirect);
-    check_required_feature!(available, required, draw_indirect_first_instance);
-    check_required_feature!(available, required, depth_clamp);
-    check_required_feature!(available, required, depth_bias_clamp);
-    check_required_feature!(available, required, fill_mode_non_solid);
-    check_required_feature!(available, required, depth_bounds);
-    check_required_feature!(available, required, wide_lines);
-    check_required_feature!(available, required, large_points);
-    check_required_feature!(available, required, alpha_to_one);
-    check_required_feature!(available, required, multi_viewport);
-    check_required_feature!(available, required, sampler_anisotropy);
-    check_required_feature!(available, required, texture_compression_etc2);
-    check_required_feature!(available, required, texture_compression_astc_ldr);
-    check_required_feature!(available, required, texture_compression_bc);
-    check_required_feature!(available, required, occlusion_query_precise);
-    check_required_feature!(available, required, pipeline_statistics_query);
-    check_required_feature!(available, required, vertex_pipeline_stores_and_atomics);
-    check_required_feature!(available, required, fragment_stores_and_atomics);
-    check_required_feature!(
-        available,
-        required,
-        shader_tessellation_and_geometry_point_size
-    );
-    check_required_feature!(available, required, shader_image_gather_extended);
-    check_required_feature!(available, required, shader_storage_image_extended_formats);
-    check_required_feature!(available, required, shader_storage_image_multisample);
-    check_required_feature!(
-        available,
-        required,
-        shader_storage_image_read_without_format
-    );
-    check_required_feature!(
-        available,
-        required,
-        shader_storage_image_write_without_format
-    );
-    check_required_feature!(
-        available,
-        required,
-        shader_uniform_buffer_array_dynamic_indexing
-    );
-    check_required_feature!(
-        available,
-        required,
-        shader_sampled_image_array_dynamic_indexing
-    );
-    check_required_feature!(
-        available,
-        required,
-        shader_storage_buffer_array_dynamic_indexing
-    );
-    check_required_feature!(
-        available,
-        required,
-        shader_storage_image_array_dynamic_indexing
-    );
-    check_required_feature!(available, required, shader_clip_distance);
-    check_required_feature!(available, required, shader_cull_distance);
-    check_required_feature!(available, required, shader_float64);
-    check_required_feature!(available, required, shader_int64);
-    check_required_feature!(available, required, shader_int16);
-    check_required_feature!(available, required, shader_resource_residency);
-    check_required_feature!(available, required, shader_resource_min_lod);
-    check_required_feature!(available, required, sparse_binding);
-    check_required_feature!(available, required, sparse_residency_buffer);
-    check_required_feature!(available, required, sparse_residency_image2_d);
-    check_required_feature!(available, required, sparse_residency_image3_d);
-    check_required_feature!(available, required, sparse_residency2_samples);
-    check_required_feature!(available, required, sparse_residency4_samples);
-    check_required_feature!(available, required, sparse_residency8_samples);
-    check_required_feature!(available, required, sparse_residency16_samples);
-    check_required_feature!(available, required, sparse_residency_aliased);
-    check_required_feature!(available, required, variable_multisample_rate);
-    check_required_feature!(available, required, inherited_queries);
-
-    true
+    let properties = instance
+        .instance()
+        .get_physical_device_format_properties(physical_device, format);
+    format_supports(&properties, tiling, features)
 }
 
-macro_rules! maybe_enable_feature {
-    ($available:ident, $optional:ident, $field:ident) => {
-        $available.$field = if $available.$field != 0 {
-            $optional.$field
-        } else {
-            0
-        }
+fn format_supports(
+    properties: &vk::FormatProperties,
+    tiling: vk::ImageTiling,
+    features: vk::FormatFeatureFlags,
+) -> bool {
+    let supported = match tiling {
+        vk::ImageTiling::LINEAR => properties.linear_tiling_features,
+        vk::ImageTiling::OPTIMAL => properties.optimal_tiling_features,
+        _ => vk::FormatFeatureFlags::empty(),
     };
+    supported.contains(features)
+}
+
+/// The first candidate supporting `features` with `tiling`, going by the
+/// properties `properties` reports for each format.
+fn first_supported_format(
+    candidates: &[vk::Format],
+    tiling: vk::ImageTiling,
+    features: vk::FormatFeatureFlags,
+    properties: impl Fn(vk::Format) -> vk::FormatProperties,
+) -> Option<vk::Format> {
+    candidates
+        .iter()
+        .copied()
+        .find(|&format| format_supports(&properties(format), tiling, features))
+}
+
+/// The core features requested through extension structs that were promoted
+/// by `api_version`.
+fn promoted_features(extension_features: &[ExtensionFeatures], api_version: u32) -> Features {
+    extension_features
+        .iter()
+        .filter(|features| features.is_core(api_version))
+        .fold(Features::new(), |all, features| {
+            all.union(&features.core_features())
+        })
+}
+
+/// The extension feature structs to chain onto `VkDeviceCreateInfo`. Promoted
+/// structs are left out since they may not be chained alongside the
+/// per-version structs, and so are those of extensions `is_enabled` rejects.
+fn device_extension_feature_chain(
+    extension_features: &[ExtensionFeatures],
+    api_version: u32,
+    is_enabled: impl Fn(&CStr) -> bool + Copy,
+) -> FeatureChain {
+    let chained: Vec<ExtensionFeatures> = extension_features
+        .iter()
+        .filter(|features| {
+            !features.is_core(api_version) && features.is_known(api_version, is_enabled)
+        })
+        .cloned()
+        .collect();
+    FeatureChain::new(&chained)
+}
+
+/// Link the per-version structs the device knows about and the extension
+/// structs into a `pNext` chain. Both must outlive any use of the result.
+fn device_feature_chain(
+    features: &mut Features,
+    extension_chain: &mut FeatureChain,
+    api_version: u32,
+) -> *mut c_void {
+    let mut p_next: *mut c_void = ptr::null_mut();
+    if api_version >= vk::API_VERSION_1_3 {
+        features.vulkan13.p_next = p_next;
+        p_next = &mut features.vulkan13 as *mut _ as *mut c_void;
+    }
+    if api_version >= vk::API_VERSION_1_2 {
+        features.vulkan12.p_next = p_next;
+        p_next = &mut features.vulkan12 as *mut _ as *mut c_void;
+        features.vulkan11.p_next = p_next;
+        p_next = &mut features.vulkan11 as *mut _ as *mut c_void;
+    }
+    extension_chain.prepend(p_next)
+}
+
+/// Orders device types for selection. Integrated GPUs report shared system
+/// memory as device-local, so the type goes before the amount of memory.
+fn device_type_rank(device_type: vk::PhysicalDeviceType) -> u8 {
+    match device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+        vk::PhysicalDeviceType::CPU => 1,
+        _ => 0,
+    }
+}
+
+fn find_memory_type(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    type_bits: u32,
+    required: vk::MemoryPropertyFlags,
+    preferred: vk::MemoryPropertyFlags,
+) -> Option<u32> {
+    let memory_types =
+        &memory_properties.memory_types[..memory_properties.memory_type_count as usize];
+    let find = |flags: vk::MemoryPropertyFlags| {
+        memory_types
+            .iter()
+            .enumerate()
+            .position(|(i, ty)| type_bits & (1 << i) != 0 && ty.property_flags.contains(flags))
+            .map(|i| i as u32)
+    };
+    find(required | preferred).or_else(|| find(required))
+}
+
+/// The budget of each heap, falling back to the heap sizes without a budget.
+fn heap_budgets(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    budget: Option<&vk::PhysicalDeviceMemoryBudgetPropertiesEXT>,
+) -> Vec<HeapBudget> {
+    let heaps = &memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize];
+    match budget {
+        Some(budget) => (0..heaps.len())
+            .map(|i| HeapBudget {
+                budget: budget.heap_budget[i],
+                usage: budget.heap_usage[i],
+            })
+            .collect(),
+        None => heaps
+            .iter()
+            .map(|heap| HeapBudget {
+                budget: heap.size,
+                usage: 0,
+            })
+            .collect(),
+    }
+}
+
+unsafe fn effective_api_version(instance: &Instance, device: vk::PhysicalDevice) -> u32 {
+    let properties = instance.instance().get_physical_device_properties(device);
+    without_patch(properties.api_version).min(instance.api_version())
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_device(None);
+        }
+    }
 }
 
-fn enable_optional_features(
-    available: &mut vk::PhysicalDeviceFeatures,
-    optional: &vk::PhysicalDeviceFeatures,
-) {
-    maybe_enable_feature!(available, optional, robust_buffer_access);
-    maybe_enable_feature!(available, optional, full_draw_index_uint32);
-    maybe_enable_feature!(available, optional, image_cube_array);
-    maybe_enable_feature!(available, optional, independent_blend);
-    maybe_enable_feature!(available, optional, geometry_shader);
-    maybe_enable_feature!(available, optional, tessellation_shader);
-    maybe_enable_feature!(available, optional, sample_rate_shading);
-    maybe_enable_feature!(available, optional, dual_src_blend);
-    maybe_enable_feature!(available, optional, logic_op);
-    maybe_enable_feature!(available, optional, multi_draw_indirect);
-    maybe_enable_feature!(available, optional, draw_indirect_first_instance);
-    maybe_enable_feature!(available, optional, depth_clamp);
-    maybe_enable_feature!(available, optional, depth_bias_clamp);
-    maybe_enable_feature!(available, optional, fill_mode_non_solid);
-    maybe_enable_feature!(available, optional, depth_bounds);
-    maybe_enable_feature!(available, optional, wide_lines);
-    maybe_enable_feature!(available, optional, large_points);
-    maybe_enable_feature!(available, optional, alpha_to_one);
-    maybe_enable_feature!(available, optional, multi_viewport);
-    maybe_enable_feature!(available, optional, sampler_anisotropy);
-    maybe_enable_feature!(available, optional, texture_compression_etc2);
-    maybe_enable_feature!(available, optional, texture_compression_astc_ldr);
-    maybe_enable_feature!(available, optional, texture_compression_bc);
-    maybe_enable_feature!(available, optional, occlusion_query_precise);
-    maybe_enable_feature!(available, optional, pipeline_statistics_query);
-    maybe_enable_feature!(available, optional, vertex_pipeline_stores_and_atomics);
-    maybe_enable_feature!(available, optional, fragment_stores_and_atomics);
-    maybe_enable_feature!(
-        available,
-        optional,
-        shader_tessellation_and_geometry_point_size
-    );
-    maybe_enable_feature!(available, optional, shader_image_gather_extended);
-    maybe_enable_feature!(available, optional, shader_storage_image_extended_formats);
-    maybe_enable_feature!(available, optional, shader_storage_image_multisample);
-    maybe_enable_feature!(
-        available,
-        optional,
-        shader_storage_image_read_without_format
-    );
-    maybe_enable_feature!(
-        available,
-        optional,
-        shader_storage_image_write_without_format
-    );
-    maybe_enable_feature!(
-        available,
-        optional,
-        shader_uniform_buffer_array_dynamic_indexing
-    );
-    maybe_enable_feature!(
-        available,
-        optional,
-        shader_sampled_image_array_dynamic_indexing
-    );
-    maybe_enable_feature!(
-        available,
-        optional,
-        shader_storage_buffer_array_dynamic_indexing
-    );
-    maybe_enable_feature!(
-        available,
-        optional,
-        shader_storage_image_array_dynamic_indexing
-    );
-    maybe_enable_feature!(available, optional, shader_clip_distance);
-    maybe_enable_feature!(available, optional, shader_cull_distance);
-    maybe_enable_feature!(available, optional, shader_float64);
-    maybe_enable_feature!(available, optional, shader_int64);
-    maybe_enable_feature!(available, optional, shader_int16);
-    maybe_enable_feature!(available, optional, shader_resource_residency);
-    maybe_enable_feature!(available, optional, shader_resource_min_lod);
-    maybe_enable_feature!(available, optional, sparse_binding);
-    maybe_enable_feature!(available, optional, sparse_residency_buffer);
-    maybe_enable_feature!(available, optional, sparse_residency_image2_d);
-    maybe_enable_feature!(available, optional, sparse_residency_image3_d);
-    maybe_enable_feature!(available, optional, sparse_residency2_samples);
-    maybe_enable_feature!(available, optional, sparse_residency4_samples);
-    maybe_enable_feature!(available, optional, sparse_residency8_samples);
-    maybe_enable_feature!(available, optional, sparse_residency16_samples);
-    maybe_enable_feature!(available, optional, sparse_residency_aliased);
-    maybe_enable_feature!(available, optional, variable_multisample_rate);
-    maybe_enable_feature!(available, optional, inherited_queries);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain_types(mut p_next: *const c_void) -> Vec<vk::StructureType> {
+        let mut types = Vec::new();
+        while !p_next.is_null() {
+            let header = unsafe { &*(p_next as *const vk::BaseInStructure) };
+            types.push(header.s_type);
+            p_next = header.p_next as *const c_void;
+        }
+        types
+    }
+
+    fn report(
+        index: u32,
+        device_type: vk::PhysicalDeviceType,
+        device_local_memory: vk::DeviceSize,
+    ) -> DeviceReport {
+        DeviceReport {
+            physical_device: vk::PhysicalDevice::null(),
+            index,
+            name: format!("device {}", index),
+            device_uuid: [index as u8 + 1; vk::UUID_SIZE],
+            device_type,
+            score: 0,
+            device_local_memory,
+            failures: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn device_selection_order() {
+        const GIB: vk::DeviceSize = 1 << 30;
+        let mut reports = vec![
+            report(0, vk::PhysicalDeviceType::CPU, 64 * GIB),
+            report(1, vk::PhysicalDeviceType::INTEGRATED_GPU, 32 * GIB),
+            report(2, vk::PhysicalDeviceType::DISCRETE_GPU, 8 * GIB),
+            report(3, vk::PhysicalDeviceType::DISCRETE_GPU, 8 * GIB),
+        ];
+        let select = |builder: &DeviceBuilder, reports: &[DeviceReport]| {
+            builder.select_physical_device(reports).map(|r| r.index)
+        };
+        let builder = DeviceBuilder::new();
+
+        // Discrete beats integrated despite its shared memory, and ties go
+        // to the device enumerated first.
+        assert_eq!(select(&builder, &reports), Some(2));
+        reports[3].device_local_memory = 16 * GIB;
+        assert_eq!(select(&builder, &reports), Some(3));
+        reports[1].score = 1;
+        assert_eq!(select(&builder, &reports), Some(1));
+        reports[1].failures.push(Unsuitability::NoGraphicsQueue);
+        assert_eq!(select(&builder, &reports), Some(3));
+
+        let builder = DeviceBuilder::new().prefer_device(PreferredDevice::Cpu);
+        assert_eq!(select(&builder, &reports), Some(0));
+        let builder = DeviceBuilder::new().prefer_device(PreferredDevice::Chosen(2));
+        assert_eq!(select(&builder, &reports), Some(2));
+        let builder = DeviceBuilder::new().prefer_device(PreferredDevice::Uuid([3; vk::UUID_SIZE]));
+        assert_eq!(select(&builder, &reports), Some(2));
+
+        for report in &mut reports {
+            report.failures.push(Unsuitability::RejectedByScorer);
+        }
+        assert_eq!(select(&builder, &reports), None);
+    }
+
+    #[test]
+    fn memory_type_preference() {
+        let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+            memory_type_count: 3,
+            ..Default::default()
+        };
+        memory_properties.memory_types[0].property_flags = vk::MemoryPropertyFlags::DEVICE_LOCAL;
+        memory_properties.memory_types[1].property_flags =
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        memory_properties.memory_types[2].property_flags = vk::MemoryPropertyFlags::DEVICE_LOCAL
+            | vk::MemoryPropertyFlags::HOST_VISIBLE
+            | vk::MemoryPropertyFlags::HOST_COHERENT;
+        // Past memory_type_count, so never picked.
+        memory_properties.memory_types[3].property_flags = vk::MemoryPropertyFlags::from_raw(!0);
+
+        let host = vk::MemoryPropertyFlags::HOST_VISIBLE;
+        let device = vk::MemoryPropertyFlags::DEVICE_LOCAL;
+        let none = vk::MemoryPropertyFlags::empty();
+        assert_eq!(
+            find_memory_type(&memory_properties, !0, device, none),
+            Some(0)
+        );
+        assert_eq!(
+            find_memory_type(&memory_properties, !0, host, none),
+            Some(1)
+        );
+        assert_eq!(
+            find_memory_type(&memory_properties, !0, host, device),
+            Some(2)
+        );
+        // Without the preferred type, any type with the required flags does.
+        assert_eq!(
+            find_memory_type(&memory_properties, 0b011, host, device),
+            Some(1)
+        );
+        assert_eq!(
+            find_memory_type(&memory_properties, 0b001, host, none),
+            None
+        );
+        assert_eq!(
+            find_memory_type(
+                &memory_properties,
+                !0,
+                vk::MemoryPropertyFlags::PROTECTED,
+                none
+            ),
+            None
+        );
+    }
+
+    fn families(flags: &[vk::QueueFlags]) -> Vec<vk::QueueFamilyProperties> {
+        flags
+            .iter()
+            .map(|&queue_flags| vk::QueueFamilyProperties {
+                queue_flags,
+                queue_count: 1,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compute_queue_prefers_dedicated_family() {
+        let graphics = vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE;
+        let compute = vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER;
+        let transfer = vk::QueueFlags::TRANSFER;
+        let find = |flags: &[vk::QueueFlags]| DeviceBuilder::find_compute_queue(&families(flags));
+
+        assert_eq!(find(&[graphics, transfer, compute]), Some(2));
+        assert_eq!(find(&[transfer, graphics]), Some(1));
+        assert_eq!(find(&[transfer]), None);
+    }
+
+    #[test]
+    fn compute_only_ignores_presentation() {
+        let surface = vk::SurfaceKHR::null();
+        let swapchain = <ash::extensions::khr::Swapchain as DeviceExtension>::name();
+        let presenting = DeviceBuilder::new().surface(surface);
+        assert!(presenting.presents());
+        assert!(presenting.required_extension_names().contains(&swapchain));
+
+        let before = DeviceBuilder::new().surface(surface).compute_only();
+        let after = DeviceBuilder::new()
+            .compute_only()
+            .surface(surface)
+            .present_queue_policy(PresentQueuePolicy::PreferCompute);
+        for builder in [before, after] {
+            assert!(!builder.presents());
+            assert!(builder.surfaces().is_empty());
+            assert!(builder.required_extension_names().is_empty());
+        }
+    }
+
+    #[test]
+    fn present_queue_policies() {
+        let queue_families = families(&[
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+            vk::QueueFlags::TRANSFER,
+        ]);
+        let pick = |supported: &[u32], policy| {
+            DeviceBuilder::pick_present_queue(&queue_families, supported, policy)
+        };
+
+        assert_eq!(
+            pick(&[0, 1, 2], PresentQueuePolicy::PreferGraphics),
+            Some(0)
+        );
+        assert_eq!(
+            pick(&[0, 1, 2], PresentQueuePolicy::PreferDedicated),
+            Some(1)
+        );
+        assert_eq!(pick(&[0, 2], PresentQueuePolicy::PreferDedicated), Some(2));
+        assert_eq!(pick(&[0, 1, 2], PresentQueuePolicy::PreferCompute), Some(1));
+        // Unsupported preferences fall back to the graphics family, then to
+        // any family that can present.
+        assert_eq!(pick(&[0], PresentQueuePolicy::PreferDedicated), Some(0));
+        assert_eq!(pick(&[0, 2], PresentQueuePolicy::PreferCompute), Some(0));
+        assert_eq!(pick(&[2], PresentQueuePolicy::PreferGraphics), Some(2));
+        assert_eq!(pick(&[], PresentQueuePolicy::PreferGraphics), None);
+    }
+
+    #[test]
+    fn queues_taken_by_the_other_protection_are_unsuitable() {
+        let flags = vk::QueueFlags::GRAPHICS | vk::QueueFlags::PROTECTED;
+        let queue_families = families(&[flags]);
+        let builder =
+            DeviceBuilder::new().request_queues(QueueRequest::new(QueueRole::Custom(0), flags));
+
+        // The default graphics queue takes the family's only queue.
+        assert_eq!(
+            builder.allocate_queues(&queue_families, [0], &[0]).err(),
+            Some(Unsuitability::QueuesTaken {
+                flags,
+                protected: true,
+            })
+        );
+        let (_, requested_queues) = builder.allocate_queues(&queue_families, [], &[0]).unwrap();
+        assert_eq!(requested_queues, vec![(QueueRole::Custom(0), 0, 0, true)]);
+    }
+
+    #[test]
+    fn heap_budgets_fall_back_to_heap_sizes() {
+        let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+            memory_heap_count: 2,
+            ..Default::default()
+        };
+        memory_properties.memory_heaps[0].size = 8 << 30;
+        memory_properties.memory_heaps[1].size = 16 << 30;
+        let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        budget.heap_budget[..3].copy_from_slice(&[6 << 30, 12 << 30, 1]);
+        budget.heap_usage[..3].copy_from_slice(&[1 << 30, 2 << 30, 1]);
+
+        let budgets = |budget| {
+            heap_budgets(&memory_properties, budget)
+                .iter()
+                .map(|heap| (heap.budget, heap.usage))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            budgets(Some(&budget)),
+            [(6 << 30, 1 << 30), (12 << 30, 2 << 30)]
+        );
+        assert_eq!(budgets(None), [(8 << 30, 0), (16 << 30, 0)]);
+    }
+
+    #[test]
+    fn unmet_limits_are_named() {
+        let builder = DeviceBuilder::new()
+            .require_limits("max_push_constants_size >= 256", |limits| {
+                limits.max_push_constants_size >= 256
+            })
+            .require_limits("max_bound_descriptor_sets >= 8", |limits| {
+                limits.max_bound_descriptor_sets >= 8
+            });
+        let limits = vk::PhysicalDeviceLimits {
+            max_push_constants_size: 128,
+            max_bound_descriptor_sets: 8,
+            ..Default::default()
+        };
+        assert_eq!(
+            builder.unmet_limits(&limits),
+            ["max_push_constants_size >= 256"]
+        );
+        let limits = vk::PhysicalDeviceLimits {
+            max_push_constants_size: 256,
+            ..limits
+        };
+        assert!(builder.unmet_limits(&limits).is_empty());
+    }
+
+    #[test]
+    fn first_supported_format_is_picked() {
+        let properties = |format| match format {
+            vk::Format::D32_SFLOAT => vk::FormatProperties {
+                linear_tiling_features: vk::FormatFeatureFlags::empty(),
+                optimal_tiling_features: vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+                ..Default::default()
+            },
+            vk::Format::D24_UNORM_S8_UINT => vk::FormatProperties {
+                linear_tiling_features: vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+                optimal_tiling_features: vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                    | vk::FormatFeatureFlags::SAMPLED_IMAGE,
+                ..Default::default()
+            },
+            _ => vk::FormatProperties::default(),
+        };
+        let candidates = [
+            vk::Format::D32_SFLOAT_S8_UINT,
+            vk::Format::D32_SFLOAT,
+            vk::Format::D24_UNORM_S8_UINT,
+        ];
+        let find =
+            |tiling, features| first_supported_format(&candidates, tiling, features, properties);
+
+        assert_eq!(
+            find(
+                vk::ImageTiling::OPTIMAL,
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+            ),
+            Some(vk::Format::D32_SFLOAT)
+        );
+        assert_eq!(
+            find(
+                vk::ImageTiling::LINEAR,
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+            ),
+            Some(vk::Format::D24_UNORM_S8_UINT)
+        );
+        // Every requested feature has to be supported.
+        assert_eq!(
+            find(
+                vk::ImageTiling::OPTIMAL,
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                    | vk::FormatFeatureFlags::SAMPLED_IMAGE
+            ),
+            Some(vk::Format::D24_UNORM_S8_UINT)
+        );
+        assert_eq!(
+            find(
+                vk::ImageTiling::OPTIMAL,
+                vk::FormatFeatureFlags::STORAGE_IMAGE
+            ),
+            None
+        );
+        assert_eq!(
+            find(
+                vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT,
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn promoted_feature_structs_are_folded() {
+        let extension_features = [
+            ExtensionFeatures::new(&vk::PhysicalDeviceTimelineSemaphoreFeatures {
+                timeline_semaphore: vk::TRUE,
+                ..Default::default()
+            }),
+            ExtensionFeatures::new(&vk::PhysicalDeviceSynchronization2Features {
+                synchronization2: vk::TRUE,
+                ..Default::default()
+            }),
+            ExtensionFeatures::new(&vk::PhysicalDeviceMeshShaderFeaturesEXT {
+                mesh_shader: vk::TRUE,
+                ..Default::default()
+            }),
+        ];
+
+        let version = vk::API_VERSION_1_1;
+        let mut features = promoted_features(&extension_features, version);
+        assert!(features.iter().next().is_none());
+        let mut chain = device_extension_feature_chain(&extension_features, version, |_| true);
+        assert_eq!(
+            chain_types(device_feature_chain(&mut features, &mut chain, version)),
+            [
+                vk::StructureType::PHYSICAL_DEVICE_TIMELINE_SEMAPHORE_FEATURES,
+                vk::StructureType::PHYSICAL_DEVICE_SYNCHRONIZATION_2_FEATURES,
+                vk::StructureType::PHYSICAL_DEVICE_MESH_SHADER_FEATURES_EXT,
+            ]
+        );
+
+        let version = vk::API_VERSION_1_2;
+        let mut features = promoted_features(&extension_features, version);
+        assert_eq!(features.vulkan12.timeline_semaphore, vk::TRUE);
+        assert_eq!(features.vulkan13.synchronization2, vk::FALSE);
+        let mut chain = device_extension_feature_chain(&extension_features, version, |_| true);
+        assert_eq!(
+            chain_types(device_feature_chain(&mut features, &mut chain, version)),
+            [
+                vk::StructureType::PHYSICAL_DEVICE_SYNCHRONIZATION_2_FEATURES,
+                vk::StructureType::PHYSICAL_DEVICE_MESH_SHADER_FEATURES_EXT,
+                vk::StructureType::PHYSICAL_DEVICE_VULKAN_1_1_FEATURES,
+                vk::StructureType::PHYSICAL_DEVICE_VULKAN_1_2_FEATURES,
+            ]
+        );
+
+        let version = vk::API_VERSION_1_3;
+        let mut features = promoted_features(&extension_features, version);
+        assert_eq!(features.vulkan12.timeline_semaphore, vk::TRUE);
+        assert_eq!(features.vulkan13.synchronization2, vk::TRUE);
+        let mut chain = device_extension_feature_chain(&extension_features, version, |_| true);
+        assert_eq!(
+            chain_types(device_feature_chain(&mut features, &mut chain, version)),
+            [
+                vk::StructureType::PHYSICAL_DEVICE_MESH_SHADER_FEATURES_EXT,
+                vk::StructureType::PHYSICAL_DEVICE_VULKAN_1_1_FEATURES,
+                vk::StructureType::PHYSICAL_DEVICE_VULKAN_1_2_FEATURES,
+                vk::StructureType::PHYSICAL_DEVICE_VULKAN_1_3_FEATURES,
+            ]
+        );
+        // Without their extensions, only the promoted structs remain.
+        let version = vk::API_VERSION_1_1;
+        let mut features = promoted_features(&extension_features, version);
+        let mut chain = device_extension_feature_chain(&extension_features, version, |name| {
+            name == vk::KhrTimelineSemaphoreFn::name()
+        });
+        assert_eq!(
+            chain_types(device_feature_chain(&mut features, &mut chain, version)),
+            [vk::StructureType::PHYSICAL_DEVICE_TIMELINE_SEMAPHORE_FEATURES]
+        );
+        let mut chain = device_extension_feature_chain(&extension_features, version, |_| false);
+        assert!(chain_types(device_feature_chain(&mut features, &mut chain, version)).is_empty());
+    }
 }
@@ -1,11 +1,32 @@
 use thiserror::Error;
 
+use crate::{util::version_string, DeviceReport};
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Vulkan Loading Error")]
     LoadingError(#[from] ash::LoadingError),
     #[error("Vulkan Error")]
     VulkanError(#[from] ash::vk::Result),
-    #[error("No Suitable Devices Found")]
-    NoSuitableDevices,
+    #[error("Required Layer Not Present: {0}")]
+    LayerNotPresent(String),
+    #[error("Unknown Feature: {0}")]
+    UnknownFeature(String),
+    #[error(
+        "Instance Version Too Low: {} is required, the loader supports {}",
+        version_string(*.required),
+        version_string(*.available)
+    )]
+    InstanceVersionTooLow { required: u32, available: u32 },
+    #[error(
+        "No Suitable Devices Found: {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    NoSuitableDevices(Vec<DeviceReport>),
+    #[error("Invalid Queue Priority: {0} is outside [0, 1]")]
+    InvalidQueuePriority(f32),
+    #[error("Present Queue Requested Without A Surface Or Present Target")]
+    NothingToPresentTo,
+    #[error("No Queue Can Present To The Surface")]
+    NoPresentQueue,
 }
@@ -1,20 +1,34 @@
-use std::os::raw::c_char;
+use std::{
+    cell::Cell,
+    ffi::{CStr, CString},
+    mem,
+    os::raw::c_char,
+};
 
-use ash::{Device, Entry, Instance};
+use ash::{vk, Device, Entry, Instance};
 
+use crate::util::streq;
+
+/// An instance extension loaded by [`InstanceBuilder`](crate::InstanceBuilder).
+/// The loaded value is kept in the [`Instance`](crate::Instance), which is
+/// shared between threads, so it has to be `Send` and `Sync`.
 pub trait InstanceExtension {
     fn name() -> *const c_char;
-    fn load(entry: &Entry, instance: &Instance) -> Box<dyn std::any::Any + 'static>;
+    fn load(entry: &Entry, instance: &Instance) -> Box<dyn std::any::Any + Send + Sync + 'static>;
 }
 pub type InstanceExtensionLoader =
-    Box<dyn FnOnce(&ash::Entry, &ash::Instance) -> Box<dyn std::any::Any + 'static>>;
+    Box<dyn FnOnce(&ash::Entry, &ash::Instance) -> Box<dyn std::any::Any + Send + Sync + 'static>>;
 
+/// A device extension loaded by [`DeviceBuilder`](crate::DeviceBuilder). The
+/// loaded value is kept in the [`Device`](crate::Device), which is shared
+/// between the threads using its queues, so it has to be `Send` and `Sync`.
 pub trait DeviceExtension {
     fn name() -> *const c_char;
-    fn load(instance: &Instance, device: &Device) -> Box<dyn std::any::Any + 'static>;
+    fn load(instance: &Instance, device: &Device)
+        -> Box<dyn std::any::Any + Send + Sync + 'static>;
 }
 pub type DeviceExtensionLoader =
-    Box<dyn FnOnce(&ash::Instance, &ash::Device) -> Box<dyn std::any::Any + 'static>>;
+    Box<dyn FnOnce(&ash::Instance, &ash::Device) -> Box<dyn std::any::Any + Send + Sync + 'static>>;
 
 macro_rules! impl_instance_extension {
     ($ext:ty) => {
@@ -23,7 +37,10 @@ macro_rules! impl_instance_extension {
                 Self::name().as_ptr()
             }
 
-            fn load(entry: &Entry, instance: &Instance) -> Box<dyn std::any::Any + 'static> {
+            fn load(
+                entry: &Entry,
+                instance: &Instance,
+            ) -> Box<dyn std::any::Any + Send + Sync + 'static> {
                 Box::new(Self::new(entry, instance))
             }
         }
@@ -37,7 +54,10 @@ macro_rules! impl_device_extension {
                 Self::name().as_ptr()
             }
 
-            fn load(instance: &Instance, device: &Device) -> Box<dyn std::any::Any + 'static> {
+            fn load(
+                instance: &Instance,
+                device: &Device,
+            ) -> Box<dyn std::any::Any + Send + Sync + 'static> {
                 Box::new(Self::new(instance, device))
             }
         }
@@ -108,7 +128,500 @@ impl InstanceExtension for ash::extensions::ext::PhysicalDeviceDrm {
         Self::name().as_ptr()
     }
 
-    fn load(_: &Entry, _: &Instance) -> Box<dyn std::any::Any> {
+    fn load(_: &Entry, _: &Instance) -> Box<dyn std::any::Any + Send + Sync> {
         Box::new(Self)
     }
 }
+
+// VK_EXT_memory_budget has no commands, so there's nothing to load.
+impl DeviceExtension for vk::ExtMemoryBudgetFn {
+    fn name() -> *const c_char {
+        Self::name().as_ptr()
+    }
+
+    fn load(_: &Instance, _: &Device) -> Box<dyn std::any::Any + Send + Sync> {
+        Box::new(Self {})
+    }
+}
+
+/// Extensions whose functionality is part of core Vulkan since the given version.
+const PROMOTED_EXTENSIONS: &[(&CStr, u32)] = &[
+    (vk::ExtExtendedDynamicStateFn::name(), vk::API_VERSION_1_3),
+    (vk::ExtExtendedDynamicState2Fn::name(), vk::API_VERSION_1_3),
+    (vk::ExtDescriptorIndexingFn::name(), vk::API_VERSION_1_2),
+    (vk::ExtPrivateDataFn::name(), vk::API_VERSION_1_3),
+    (vk::KhrBufferDeviceAddressFn::name(), vk::API_VERSION_1_2),
+    (vk::KhrCopyCommands2Fn::name(), vk::API_VERSION_1_3),
+    (vk::KhrCreateRenderpass2Fn::name(), vk::API_VERSION_1_2),
+    (vk::KhrDedicatedAllocationFn::name(), vk::API_VERSION_1_1),
+    (vk::KhrDepthStencilResolveFn::name(), vk::API_VERSION_1_2),
+    (vk::KhrDeviceGroupFn::name(), vk::API_VERSION_1_1),
+    (vk::KhrDeviceGroupCreationFn::name(), vk::API_VERSION_1_1),
+    (vk::KhrDrawIndirectCountFn::name(), vk::API_VERSION_1_2),
+    (vk::KhrDynamicRenderingFn::name(), vk::API_VERSION_1_3),
+    (vk::KhrExternalFenceFn::name(), vk::API_VERSION_1_1),
+    (
+        vk::KhrExternalFenceCapabilitiesFn::name(),
+        vk::API_VERSION_1_1,
+    ),
+    (vk::KhrExternalMemoryFn::name(), vk::API_VERSION_1_1),
+    (
+        vk::KhrExternalMemoryCapabilitiesFn::name(),
+        vk::API_VERSION_1_1,
+    ),
+    (vk::KhrExternalSemaphoreFn::name(), vk::API_VERSION_1_1),
+    (
+        vk::KhrExternalSemaphoreCapabilitiesFn::name(),
+        vk::API_VERSION_1_1,
+    ),
+    (vk::KhrGetMemoryRequirements2Fn::name(), vk::API_VERSION_1_1),
+    (PROPERTIES2, vk::API_VERSION_1_1),
+    (vk::KhrMaintenance1Fn::name(), vk::API_VERSION_1_1),
+    (vk::KhrMaintenance2Fn::name(), vk::API_VERSION_1_1),
+    (vk::KhrMaintenance3Fn::name(), vk::API_VERSION_1_1),
+    (vk::KhrMaintenance4Fn::name(), vk::API_VERSION_1_3),
+    (vk::KhrMultiviewFn::name(), vk::API_VERSION_1_1),
+    (vk::KhrShaderFloatControlsFn::name(), vk::API_VERSION_1_2),
+    (vk::KhrSpirv14Fn::name(), vk::API_VERSION_1_2),
+    (vk::KhrSynchronization2Fn::name(), vk::API_VERSION_1_3),
+    (vk::KhrTimelineSemaphoreFn::name(), vk::API_VERSION_1_2),
+];
+
+const PROPERTIES2: &CStr = vk::KhrGetPhysicalDeviceProperties2Fn::name();
+const SURFACE: &CStr = vk::KhrSurfaceFn::name();
+const SWAPCHAIN: &CStr = vk::KhrSwapchainFn::name();
+
+/// The extensions each extension requires, as `(extension, device, instance)`.
+/// Instance extensions only ever depend on other instance extensions.
+const EXTENSION_DEPENDENCIES: &[(&CStr, &[&CStr], &[&CStr])] = &[
+    // Instance extensions.
+    (vk::KhrAndroidSurfaceFn::name(), &[], &[SURFACE]),
+    (vk::KhrDisplayFn::name(), &[], &[SURFACE]),
+    (
+        vk::KhrExternalFenceCapabilitiesFn::name(),
+        &[],
+        &[PROPERTIES2],
+    ),
+    (
+        vk::KhrExternalMemoryCapabilitiesFn::name(),
+        &[],
+        &[PROPERTIES2],
+    ),
+    (
+        vk::KhrExternalSemaphoreCapabilitiesFn::name(),
+        &[],
+        &[PROPERTIES2],
+    ),
+    (vk::KhrGetSurfaceCapabilities2Fn::name(), &[], &[SURFACE]),
+    (vk::KhrWaylandSurfaceFn::name(), &[], &[SURFACE]),
+    (vk::KhrWin32SurfaceFn::name(), &[], &[SURFACE]),
+    (vk::KhrXcbSurfaceFn::name(), &[], &[SURFACE]),
+    (vk::KhrXlibSurfaceFn::name(), &[], &[SURFACE]),
+    (vk::ExtMetalSurfaceFn::name(), &[], &[SURFACE]),
+    (vk::MvkIosSurfaceFn::name(), &[], &[SURFACE]),
+    (vk::MvkMacosSurfaceFn::name(), &[], &[SURFACE]),
+    (vk::NnViSurfaceFn::name(), &[], &[SURFACE]),
+    // Device extensions.
+    (vk::ExtBufferDeviceAddressFn::name(), &[], &[PROPERTIES2]),
+    (
+        vk::ExtDescriptorIndexingFn::name(),
+        &[vk::KhrMaintenance3Fn::name()],
+        &[PROPERTIES2],
+    ),
+    (vk::ExtExtendedDynamicStateFn::name(), &[], &[PROPERTIES2]),
+    (vk::ExtExtendedDynamicState2Fn::name(), &[], &[PROPERTIES2]),
+    (
+        vk::ExtFullScreenExclusiveFn::name(),
+        &[SWAPCHAIN],
+        &[PROPERTIES2, vk::KhrGetSurfaceCapabilities2Fn::name()],
+    ),
+    (vk::ExtMemoryBudgetFn::name(), &[], &[PROPERTIES2]),
+    (vk::ExtPrivateDataFn::name(), &[], &[PROPERTIES2]),
+    (
+        vk::KhrAccelerationStructureFn::name(),
+        &[
+            vk::ExtDescriptorIndexingFn::name(),
+            vk::KhrBufferDeviceAddressFn::name(),
+            vk::KhrDeferredHostOperationsFn::name(),
+        ],
+        &[],
+    ),
+    (
+        vk::KhrBufferDeviceAddressFn::name(),
+        &[vk::KhrDeviceGroupFn::name()],
+        &[PROPERTIES2],
+    ),
+    (vk::KhrCopyCommands2Fn::name(), &[], &[PROPERTIES2]),
+    (
+        vk::KhrCreateRenderpass2Fn::name(),
+        &[vk::KhrMultiviewFn::name(), vk::KhrMaintenance2Fn::name()],
+        &[],
+    ),
+    (
+        vk::KhrDepthStencilResolveFn::name(),
+        &[vk::KhrCreateRenderpass2Fn::name()],
+        &[],
+    ),
+    (
+        vk::KhrDeviceGroupFn::name(),
+        &[],
+        &[vk::KhrDeviceGroupCreationFn::name()],
+    ),
+    (
+        vk::KhrDisplaySwapchainFn::name(),
+        &[SWAPCHAIN],
+        &[vk::KhrDisplayFn::name()],
+    ),
+    (
+        vk::KhrDynamicRenderingFn::name(),
+        &[vk::KhrDepthStencilResolveFn::name()],
+        &[PROPERTIES2],
+    ),
+    (
+        vk::KhrExternalFenceFn::name(),
+        &[],
+        &[vk::KhrExternalFenceCapabilitiesFn::name()],
+    ),
+    (
+        vk::KhrExternalFenceFdFn::name(),
+        &[vk::KhrExternalFenceFn::name()],
+        &[],
+    ),
+    (
+        vk::KhrExternalFenceWin32Fn::name(),
+        &[vk::KhrExternalFenceFn::name()],
+        &[],
+    ),
+    (
+        vk::KhrExternalMemoryFn::name(),
+        &[],
+        &[vk::KhrExternalMemoryCapabilitiesFn::name()],
+    ),
+    (
+        vk::KhrExternalMemoryFdFn::name(),
+        &[vk::KhrExternalMemoryFn::name()],
+        &[],
+    ),
+    (
+        vk::KhrExternalMemoryWin32Fn::name(),
+        &[vk::KhrExternalMemoryFn::name()],
+        &[],
+    ),
+    (
+        vk::KhrExternalSemaphoreFn::name(),
+        &[],
+        &[vk::KhrExternalSemaphoreCapabilitiesFn::name()],
+    ),
+    (
+        vk::KhrExternalSemaphoreFdFn::name(),
+        &[vk::KhrExternalSemaphoreFn::name()],
+        &[],
+    ),
+    (
+        vk::KhrExternalSemaphoreWin32Fn::name(),
+        &[vk::KhrExternalSemaphoreFn::name()],
+        &[],
+    ),
+    (vk::KhrMaintenance3Fn::name(), &[], &[PROPERTIES2]),
+    (vk::KhrMultiviewFn::name(), &[], &[PROPERTIES2]),
+    (
+        vk::KhrPipelineExecutablePropertiesFn::name(),
+        &[],
+        &[PROPERTIES2],
+    ),
+    (vk::KhrPresentIdFn::name(), &[SWAPCHAIN], &[PROPERTIES2]),
+    (
+        vk::KhrPresentWaitFn::name(),
+        &[SWAPCHAIN, vk::KhrPresentIdFn::name()],
+        &[],
+    ),
+    (vk::KhrPushDescriptorFn::name(), &[], &[PROPERTIES2]),
+    (
+        vk::KhrRayTracingPipelineFn::name(),
+        &[
+            vk::KhrSpirv14Fn::name(),
+            vk::KhrAccelerationStructureFn::name(),
+        ],
+        &[],
+    ),
+    (vk::KhrShaderFloatControlsFn::name(), &[], &[PROPERTIES2]),
+    (
+        vk::KhrSpirv14Fn::name(),
+        &[vk::KhrShaderFloatControlsFn::name()],
+        &[],
+    ),
+    (vk::KhrSwapchainFn::name(), &[], &[SURFACE]),
+    (vk::KhrSynchronization2Fn::name(), &[], &[PROPERTIES2]),
+    (vk::KhrTimelineSemaphoreFn::name(), &[], &[PROPERTIES2]),
+    (vk::NvMeshShaderFn::name(), &[], &[PROPERTIES2]),
+    (
+        vk::NvRayTracingFn::name(),
+        &[vk::KhrGetMemoryRequirements2Fn::name()],
+        &[PROPERTIES2],
+    ),
+];
+
+/// Everything an extension transitively depends on, excluding extensions that
+/// are part of the API version in use.
+#[derive(Default)]
+pub(crate) struct Dependencies {
+    pub device: Vec<*const c_char>,
+    pub instance: Vec<*const c_char>,
+}
+
+impl Dependencies {
+    /// Resolve the dependencies of `name`, which may be either a device or an
+    /// instance extension.
+    pub(crate) unsafe fn of(
+        name: *const c_char,
+        device_version: u32,
+        instance_version: u32,
+    ) -> Dependencies {
+        let mut deps = Dependencies::default();
+        let mut pending = vec![name];
+        while let Some(name) = pending.pop() {
+            let Some((_, device, instance)) = EXTENSION_DEPENDENCIES
+                .iter()
+                .find(|(ext, _, _)| streq(ext.as_ptr(), name))
+            else {
+                continue;
+            };
+
+            for dep in device.iter().map(|dep| dep.as_ptr()) {
+                if !is_promoted(dep, device_version) && !contains_name(&deps.device, dep) {
+                    deps.device.push(dep);
+                    pending.push(dep);
+                }
+            }
+            for dep in instance.iter().map(|dep| dep.as_ptr()) {
+                if !is_promoted(dep, instance_version) && !contains_name(&deps.instance, dep) {
+                    deps.instance.push(dep);
+                    pending.push(dep);
+                }
+            }
+        }
+
+        deps
+    }
+}
+
+pub(crate) unsafe fn contains_name(names: &[*const c_char], name: *const c_char) -> bool {
+    names.iter().any(|n| streq(*n, name))
+}
+
+/// Whether `api_version` already includes the extension's functionality, so
+/// it doesn't need to be enabled.
+pub(crate) unsafe fn is_promoted(name: *const c_char, api_version: u32) -> bool {
+    PROMOTED_EXTENSIONS
+        .iter()
+        .any(|(ext, version)| *version <= api_version && streq(ext.as_ptr(), name))
+}
+
+// Extension loaders look up the suffixed entry points (e.g. vkCmdPipelineBarrier2KHR),
+// which drivers don't have to expose unless the extension is enabled. When the
+// functionality comes from core instead, the loaders get an `Entry` or
+// `Instance` whose lookups try the core name first. Loaders only call plain
+// function pointers, so the lookup functions of the instance being loaded are
+// kept here while its loaders run, and cleared right after.
+thread_local! {
+    static PROC_ADDRS: Cell<Option<ProcAddrs>> = const { Cell::new(None) };
+}
+
+#[derive(Clone, Copy)]
+struct ProcAddrs {
+    get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    get_device_proc_addr: Option<vk::PFN_vkGetDeviceProcAddr>,
+}
+
+/// Restores the previous lookup functions, even if a loader panics.
+struct ProcAddrsGuard(Option<ProcAddrs>);
+
+impl Drop for ProcAddrsGuard {
+    fn drop(&mut self) {
+        PROC_ADDRS.with(|addrs| addrs.set(self.0));
+    }
+}
+
+fn with_proc_addrs<R>(proc_addrs: ProcAddrs, f: impl FnOnce() -> R) -> R {
+    let _guard = ProcAddrsGuard(PROC_ADDRS.with(|addrs| addrs.replace(Some(proc_addrs))));
+    f()
+}
+
+/// Run `load` with an `Entry` for loading instance extensions that were
+/// promoted to core instead of enabled, so their loaders resolve the core
+/// entry points. Enabled extensions are still loaded from the real `Entry`.
+/// The `Entry` must not be used after `load` returns.
+pub(crate) unsafe fn with_core_entry<R>(entry: &Entry, load: impl FnOnce(&Entry) -> R) -> R {
+    let proc_addrs = ProcAddrs {
+        get_instance_proc_addr: entry.static_fn().get_instance_proc_addr,
+        get_device_proc_addr: None,
+    };
+    with_proc_addrs(proc_addrs, || {
+        load(&Entry::from_static_fn(vk::StaticFn {
+            get_instance_proc_addr: core_get_instance_proc_addr,
+        }))
+    })
+}
+
+/// Run `load` with an `Instance` for loading device extensions that were
+/// promoted to core instead of enabled, like [`with_core_entry`] does for
+/// instance extensions. The `Instance` must not be used after `load` returns.
+pub(crate) unsafe fn with_core_instance<R>(
+    entry: &Entry,
+    instance: &Instance,
+    load: impl FnOnce(&Instance) -> R,
+) -> R {
+    let proc_addrs = ProcAddrs {
+        get_instance_proc_addr: entry.static_fn().get_instance_proc_addr,
+        get_device_proc_addr: Some(instance.fp_v1_0().get_device_proc_addr),
+    };
+    with_proc_addrs(proc_addrs, || {
+        load(&Instance::load(
+            &vk::StaticFn {
+                get_instance_proc_addr: core_get_instance_proc_addr,
+            },
+            instance.handle(),
+        ))
+    })
+}
+
+/// Look up the core name first, then the name as given.
+unsafe fn core_first(
+    name: *const c_char,
+    lookup: impl Fn(*const c_char) -> vk::PFN_vkVoidFunction,
+) -> vk::PFN_vkVoidFunction {
+    without_suffix(name)
+        .and_then(|core_name| lookup(core_name.as_ptr()))
+        .or_else(|| lookup(name))
+}
+
+unsafe extern "system" fn core_get_instance_proc_addr(
+    instance: vk::Instance,
+    name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    let proc_addrs = PROC_ADDRS.with(Cell::get)?;
+    if CStr::from_ptr(name) == c"vkGetDeviceProcAddr" && proc_addrs.get_device_proc_addr.is_some() {
+        let func: vk::PFN_vkGetDeviceProcAddr = core_get_device_proc_addr;
+        return Some(mem::transmute::<
+            vk::PFN_vkGetDeviceProcAddr,
+            unsafe extern "system" fn(),
+        >(func));
+    }
+
+    core_first(name, |name| {
+        (proc_addrs.get_instance_proc_addr)(instance, name)
+    })
+}
+
+unsafe extern "system" fn core_get_device_proc_addr(
+    device: vk::Device,
+    name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    let get_device_proc_addr = PROC_ADDRS.with(Cell::get)?.get_device_proc_addr?;
+    core_first(name, |name| get_device_proc_addr(device, name))
+}
+
+unsafe fn without_suffix(name: *const c_char) -> Option<CString> {
+    let name = CStr::from_ptr(name).to_bytes();
+    ["KHR", "EXT"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix.as_bytes()))
+        .map(|core| CString::new(core).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn promoted_extensions() {
+        unsafe {
+            let sync2 = vk::KhrSynchronization2Fn::name().as_ptr();
+            assert!(!is_promoted(sync2, vk::API_VERSION_1_2));
+            assert!(is_promoted(sync2, vk::API_VERSION_1_3));
+            assert!(is_promoted(PROPERTIES2.as_ptr(), vk::API_VERSION_1_1));
+            assert!(!is_promoted(SWAPCHAIN.as_ptr(), vk::API_VERSION_1_3));
+        }
+    }
+
+    #[test]
+    fn dependencies_are_transitive() {
+        let names = |names: &[*const c_char]| -> Vec<&CStr> {
+            names
+                .iter()
+                .map(|n| unsafe { CStr::from_ptr(*n) })
+                .collect()
+        };
+        let acceleration_structure = vk::KhrAccelerationStructureFn::name().as_ptr();
+
+        let deps = unsafe {
+            Dependencies::of(
+                acceleration_structure,
+                vk::API_VERSION_1_0,
+                vk::API_VERSION_1_0,
+            )
+        };
+        let device = names(&deps.device);
+        for ext in [
+            vk::ExtDescriptorIndexingFn::name(),
+            vk::KhrBufferDeviceAddressFn::name(),
+            vk::KhrDeferredHostOperationsFn::name(),
+            // Through descriptor indexing and buffer device address.
+            vk::KhrMaintenance3Fn::name(),
+            vk::KhrDeviceGroupFn::name(),
+        ] {
+            assert!(device.contains(&ext), "{:?} missing", ext);
+        }
+        assert_eq!(device.len(), 5);
+        let instance = names(&deps.instance);
+        assert!(instance.contains(&PROPERTIES2));
+        assert!(instance.contains(&vk::KhrDeviceGroupCreationFn::name()));
+        assert_eq!(instance.len(), 2);
+
+        // Everything but deferred host operations is core in 1.2.
+        let deps = unsafe {
+            Dependencies::of(
+                acceleration_structure,
+                vk::API_VERSION_1_2,
+                vk::API_VERSION_1_2,
+            )
+        };
+        assert_eq!(
+            names(&deps.device),
+            [vk::KhrDeferredHostOperationsFn::name()]
+        );
+        assert!(deps.instance.is_empty());
+    }
+
+    #[test]
+    fn core_names_are_looked_up_first() {
+        unsafe extern "system" fn entry_point() {}
+        let available: &[&CStr] = &[
+            c"vkCmdPipelineBarrier2",
+            c"vkCmdPipelineBarrier2KHR",
+            c"vkCmdDrawMeshTasksNV",
+        ];
+        let resolved = |name: &CStr| {
+            let found = std::cell::RefCell::new(None);
+            let lookup = |name: *const c_char| {
+                let name = unsafe { CStr::from_ptr(name) };
+                available.contains(&name).then(|| {
+                    found.replace(Some(name.to_owned()));
+                    entry_point as unsafe extern "system" fn()
+                })
+            };
+            unsafe { core_first(name.as_ptr(), lookup) };
+            found.into_inner()
+        };
+
+        assert_eq!(
+            resolved(c"vkCmdPipelineBarrier2KHR").as_deref(),
+            Some(c"vkCmdPipelineBarrier2")
+        );
+        assert_eq!(
+            resolved(c"vkCmdDrawMeshTasksNV").as_deref(),
+            Some(c"vkCmdDrawMeshTasksNV")
+        );
+        assert_eq!(resolved(c"vkCmdUnknownKHR"), None);
+    }
+}
@@ -0,0 +1,1037 @@
+use std::{
+    any::{self, TypeId},
+    ffi::{c_void, CStr},
+    fmt, mem, ptr,
+    str::FromStr,
+};
+
+use ash::vk;
+
+use crate::{Error, Instance, PhysicalDeviceInfo};
+
+/// Accessors for one `VkBool32` member of a feature struct.
+pub struct FeatureField<T> {
+    pub name: &'static str,
+    pub get: fn(&T) -> vk::Bool32,
+    pub set: fn(&mut T, vk::Bool32),
+}
+
+macro_rules! feature_table {
+    ($table:ident: $ty:ty { $($field:ident,)* }) => {
+        pub(crate) const $table: &[FeatureField<$ty>] = &[
+            $(FeatureField {
+                name: stringify!($field),
+                get: |features| features.$field,
+                set: |features, value| features.$field = value,
+            },)*
+        ];
+    };
+}
+
+feature_table! {
+    VULKAN_10_FEATURES: vk::PhysicalDeviceFeatures {
+        robust_buffer_access,
+        full_draw_index_uint32,
+        image_cube_array,
+        independent_blend,
+        geometry_shader,
+        tessellation_shader,
+        sample_rate_shading,
+        dual_src_blend,
+        logic_op,
+        multi_draw_indirect,
+        draw_indirect_first_instance,
+        depth_clamp,
+        depth_bias_clamp,
+        fill_mode_non_solid,
+        depth_bounds,
+        wide_lines,
+        large_points,
+        alpha_to_one,
+        multi_viewport,
+        sampler_anisotropy,
+        texture_compression_etc2,
+        texture_compression_astc_ldr,
+        texture_compression_bc,
+        occlusion_query_precise,
+        pipeline_statistics_query,
+        vertex_pipeline_stores_and_atomics,
+        fragment_stores_and_atomics,
+        shader_tessellation_and_geometry_point_size,
+        shader_image_gather_extended,
+        shader_storage_image_extended_formats,
+        shader_storage_image_multisample,
+        shader_storage_image_read_without_format,
+        shader_storage_image_write_without_format,
+        shader_uniform_buffer_array_dynamic_indexing,
+        shader_sampled_image_array_dynamic_indexing,
+        shader_storage_buffer_array_dynamic_indexing,
+        shader_storage_image_array_dynamic_indexing,
+        shader_clip_distance,
+        shader_cull_distance,
+        shader_float64,
+        shader_int64,
+        shader_int16,
+        shader_resource_residency,
+        shader_resource_min_lod,
+        sparse_binding,
+        sparse_residency_buffer,
+        sparse_residency_image2_d,
+        sparse_residency_image3_d,
+        sparse_residency2_samples,
+        sparse_residency4_samples,
+        sparse_residency8_samples,
+        sparse_residency16_samples,
+        sparse_residency_aliased,
+        variable_multisample_rate,
+        inherited_queries,
+    }
+}
+
+feature_table! {
+    VULKAN_11_FEATURES: vk::PhysicalDeviceVulkan11Features {
+        storage_buffer16_bit_access,
+        uniform_and_storage_buffer16_bit_access,
+        storage_push_constant16,
+        storage_input_output16,
+        multiview,
+        multiview_geometry_shader,
+        multiview_tessellation_shader,
+        variable_pointers_storage_buffer,
+        variable_pointers,
+        protected_memory,
+        sampler_ycbcr_conversion,
+        shader_draw_parameters,
+    }
+}
+
+feature_table! {
+    VULKAN_12_FEATURES: vk::PhysicalDeviceVulkan12Features {
+        sampler_mirror_clamp_to_edge,
+        draw_indirect_count,
+        storage_buffer8_bit_access,
+        uniform_and_storage_buffer8_bit_access,
+        storage_push_constant8,
+        shader_buffer_int64_atomics,
+        shader_shared_int64_atomics,
+        shader_float16,
+        shader_int8,
+        descriptor_indexing,
+        shader_input_attachment_array_dynamic_indexing,
+        shader_uniform_texel_buffer_array_dynamic_indexing,
+        shader_storage_texel_buffer_array_dynamic_indexing,
+        shader_uniform_buffer_array_non_uniform_indexing,
+        shader_sampled_image_array_non_uniform_indexing,
+        shader_storage_buffer_array_non_uniform_indexing,
+        shader_storage_image_array_non_uniform_indexing,
+        shader_input_attachment_array_non_uniform_indexing,
+        shader_uniform_texel_buffer_array_non_uniform_indexing,
+        shader_storage_texel_buffer_array_non_uniform_indexing,
+        descriptor_binding_uniform_buffer_update_after_bind,
+        descriptor_binding_sampled_image_update_after_bind,
+        descriptor_binding_storage_image_update_after_bind,
+        descriptor_binding_storage_buffer_update_after_bind,
+        descriptor_binding_uniform_texel_buffer_update_after_bind,
+        descriptor_binding_storage_texel_buffer_update_after_bind,
+        descriptor_binding_update_unused_while_pending,
+        descriptor_binding_partially_bound,
+        descriptor_binding_variable_descriptor_count,
+        runtime_descriptor_array,
+        sampler_filter_minmax,
+        scalar_block_layout,
+        imageless_framebuffer,
+        uniform_buffer_standard_layout,
+        shader_subgroup_extended_types,
+        separate_depth_stencil_layouts,
+        host_query_reset,
+        timeline_semaphore,
+        buffer_device_address,
+        buffer_device_address_capture_replay,
+        buffer_device_address_multi_device,
+        vulkan_memory_model,
+        vulkan_memory_model_device_scope,
+        vulkan_memory_model_availability_visibility_chains,
+        shader_output_viewport_index,
+        shader_output_layer,
+        subgroup_broadcast_dynamic_id,
+    }
+}
+
+feature_table! {
+    VULKAN_13_FEATURES: vk::PhysicalDeviceVulkan13Features {
+        robust_image_access,
+        inline_uniform_block,
+        descriptor_binding_inline_uniform_block_update_after_bind,
+        pipeline_creation_cache_control,
+        private_data,
+        shader_demote_to_helper_invocation,
+        shader_terminate_invocation,
+        subgroup_size_control,
+        compute_full_subgroups,
+        synchronization2,
+        texture_compression_astc_hdr,
+        shader_zero_initialize_workgroup_memory,
+        dynamic_rendering,
+        shader_integer_dot_product,
+        maintenance4,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum FeatureSet {
+    Vulkan10,
+    Vulkan11,
+    Vulkan12,
+    Vulkan13,
+}
+
+/// A single feature from `VkPhysicalDeviceFeatures` or one of the
+/// `VkPhysicalDeviceVulkan1XFeatures` structs, named after its ash field
+/// (`sampler_anisotropy`, `timeline_semaphore`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Feature {
+    set: FeatureSet,
+    index: usize,
+}
+
+impl Feature {
+    /// Every known feature, in struct order.
+    pub fn all() -> impl Iterator<Item = Feature> {
+        let set = |set, len| (0..len).map(move |index| Feature { set, index });
+        set(FeatureSet::Vulkan10, VULKAN_10_FEATURES.len())
+            .chain(set(FeatureSet::Vulkan11, VULKAN_11_FEATURES.len()))
+            .chain(set(FeatureSet::Vulkan12, VULKAN_12_FEATURES.len()))
+            .chain(set(FeatureSet::Vulkan13, VULKAN_13_FEATURES.len()))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self.set {
+            FeatureSet::Vulkan10 => VULKAN_10_FEATURES[self.index].name,
+            FeatureSet::Vulkan11 => VULKAN_11_FEATURES[self.index].name,
+            FeatureSet::Vulkan12 => VULKAN_12_FEATURES[self.index].name,
+            FeatureSet::Vulkan13 => VULKAN_13_FEATURES[self.index].name,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Feature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Feature::all()
+            .find(|feature| feature.name() == s)
+            .ok_or_else(|| Error::UnknownFeature(s.to_owned()))
+    }
+}
+
+/// The core features of every Vulkan version, as one set.
+#[derive(Clone, Copy, Default)]
+pub struct Features {
+    pub(crate) vulkan10: vk::PhysicalDeviceFeatures,
+    pub(crate) vulkan11: vk::PhysicalDeviceVulkan11Features,
+    pub(crate) vulkan12: vk::PhysicalDeviceVulkan12Features,
+    pub(crate) vulkan13: vk::PhysicalDeviceVulkan13Features,
+}
+
+// The pNext pointers are only set within the crate while the structs are
+// being chained, and the accessors hand out copies without them.
+unsafe impl Send for Features {}
+unsafe impl Sync for Features {}
+
+impl Features {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vulkan10(&self) -> vk::PhysicalDeviceFeatures {
+        self.vulkan10
+    }
+
+    /// Only reported by devices supporting Vulkan 1.2.
+    pub fn vulkan11(&self) -> vk::PhysicalDeviceVulkan11Features {
+        vk::PhysicalDeviceVulkan11Features {
+            p_next: ptr::null_mut(),
+            ..self.vulkan11
+        }
+    }
+
+    pub fn vulkan12(&self) -> vk::PhysicalDeviceVulkan12Features {
+        vk::PhysicalDeviceVulkan12Features {
+            p_next: ptr::null_mut(),
+            ..self.vulkan12
+        }
+    }
+
+    pub fn vulkan13(&self) -> vk::PhysicalDeviceVulkan13Features {
+        vk::PhysicalDeviceVulkan13Features {
+            p_next: ptr::null_mut(),
+            ..self.vulkan13
+        }
+    }
+
+    pub fn contains(&self, feature: Feature) -> bool {
+        let value = match feature.set {
+            FeatureSet::Vulkan10 => (VULKAN_10_FEATURES[feature.index].get)(&self.vulkan10),
+            FeatureSet::Vulkan11 => (VULKAN_11_FEATURES[feature.index].get)(&self.vulkan11),
+            FeatureSet::Vulkan12 => (VULKAN_12_FEATURES[feature.index].get)(&self.vulkan12),
+            FeatureSet::Vulkan13 => (VULKAN_13_FEATURES[feature.index].get)(&self.vulkan13),
+        };
+        value != vk::FALSE
+    }
+
+    pub fn set(&mut self, feature: Feature, enabled: bool) {
+        let value = enabled.into();
+        match feature.set {
+            FeatureSet::Vulkan10 => {
+                (VULKAN_10_FEATURES[feature.index].set)(&mut self.vulkan10, value)
+            }
+            FeatureSet::Vulkan11 => {
+                (VULKAN_11_FEATURES[feature.index].set)(&mut self.vulkan11, value)
+            }
+            FeatureSet::Vulkan12 => {
+                (VULKAN_12_FEATURES[feature.index].set)(&mut self.vulkan12, value)
+            }
+            FeatureSet::Vulkan13 => {
+                (VULKAN_13_FEATURES[feature.index].set)(&mut self.vulkan13, value)
+            }
+        }
+    }
+
+    /// The features that are turned on.
+    pub fn iter(&self) -> impl Iterator<Item = Feature> + '_ {
+        Feature::all().filter(|feature| self.contains(*feature))
+    }
+
+    /// The features turned on here but not in `other`.
+    pub fn difference(&self, other: &Features) -> Vec<Feature> {
+        self.iter()
+            .filter(|feature| !other.contains(*feature))
+            .collect()
+    }
+
+    pub fn union(&self, other: &Features) -> Features {
+        let mut union = *self;
+        for feature in other.iter() {
+            union.set(feature, true);
+        }
+        union
+    }
+
+    pub fn intersection(&self, other: &Features) -> Features {
+        let mut intersection = Features::new();
+        for feature in self.iter().filter(|feature| other.contains(*feature)) {
+            intersection.set(feature, true);
+        }
+        intersection
+    }
+}
+
+impl FromIterator<Feature> for Features {
+    fn from_iter<I: IntoIterator<Item = Feature>>(iter: I) -> Self {
+        let mut features = Features::new();
+        for feature in iter {
+            features.set(feature, true);
+        }
+        features
+    }
+}
+
+impl fmt::Debug for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// Lists the features that are turned on, separated by commas.
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, feature) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(feature.name())?;
+        }
+        Ok(())
+    }
+}
+
+/// An extension feature struct such as `vk::PhysicalDeviceMeshShaderFeaturesEXT`,
+/// for [`DeviceBuilder::require_extension_features`](crate::DeviceBuilder::require_extension_features).
+///
+/// The trait is sealed: it is implemented here for the structs listed under
+/// "Implementations on Foreign Types", which are those promoted to Vulkan 1.1
+/// through 1.3 and those of common KHR, EXT and NV extensions. Structs for
+/// other extensions can't be described here, since neither the trait nor the
+/// `vk` types belong to the crate that would implement it.
+pub trait ExtensionFeatureStruct:
+    sealed::Sealed
+    + vk::ExtendsPhysicalDeviceFeatures2
+    + vk::ExtendsDeviceCreateInfo
+    + Copy
+    + Default
+    + 'static
+{
+    /// The `VkBool32` members, in declaration order.
+    #[doc(hidden)]
+    const FIELDS: &'static [FeatureField<Self>];
+
+    /// The version whose `VkPhysicalDeviceVulkan1XFeatures` struct took over
+    /// the members, for structs promoted to core.
+    #[doc(hidden)]
+    const PROMOTED: Option<u32> = None;
+
+    /// The extension that adds the struct, if any.
+    #[doc(hidden)]
+    const EXTENSION: Option<&'static CStr> = None;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! extension_feature_structs {
+    ($($ty:ident $(($extension:ident))? $(: $version:ident)? { $($field:ident,)* })*) => {
+        $(impl sealed::Sealed for vk::$ty {}
+
+        impl ExtensionFeatureStruct for vk::$ty {
+            $(const PROMOTED: Option<u32> = Some(vk::$version);)?
+            $(const EXTENSION: Option<&'static CStr> = Some(vk::$extension::name());)?
+            const FIELDS: &'static [FeatureField<Self>] = &[
+                $(FeatureField {
+                    name: stringify!($field),
+                    get: |features| features.$field,
+                    set: |features, value| features.$field = value,
+                },)*
+            ];
+        })*
+    };
+}
+
+extension_feature_structs! {
+    PhysicalDevice16BitStorageFeatures(Khr16bitStorageFn): API_VERSION_1_1 {
+        storage_buffer16_bit_access,
+        uniform_and_storage_buffer16_bit_access,
+        storage_push_constant16,
+        storage_input_output16,
+    }
+    PhysicalDeviceMultiviewFeatures(KhrMultiviewFn): API_VERSION_1_1 {
+        multiview,
+        multiview_geometry_shader,
+        multiview_tessellation_shader,
+    }
+    PhysicalDeviceVariablePointersFeatures(KhrVariablePointersFn): API_VERSION_1_1 {
+        variable_pointers_storage_buffer,
+        variable_pointers,
+    }
+    PhysicalDeviceProtectedMemoryFeatures: API_VERSION_1_1 {
+        protected_memory,
+    }
+    PhysicalDeviceSamplerYcbcrConversionFeatures(KhrSamplerYcbcrConversionFn): API_VERSION_1_1 {
+        sampler_ycbcr_conversion,
+    }
+    PhysicalDeviceShaderDrawParametersFeatures: API_VERSION_1_1 {
+        shader_draw_parameters,
+    }
+    PhysicalDevice8BitStorageFeatures(Khr8bitStorageFn): API_VERSION_1_2 {
+        storage_buffer8_bit_access,
+        uniform_and_storage_buffer8_bit_access,
+        storage_push_constant8,
+    }
+    PhysicalDeviceShaderAtomicInt64Features(KhrShaderAtomicInt64Fn): API_VERSION_1_2 {
+        shader_buffer_int64_atomics,
+        shader_shared_int64_atomics,
+    }
+    PhysicalDeviceShaderFloat16Int8Features(KhrShaderFloat16Int8Fn): API_VERSION_1_2 {
+        shader_float16,
+        shader_int8,
+    }
+    PhysicalDeviceDescriptorIndexingFeatures(ExtDescriptorIndexingFn): API_VERSION_1_2 {
+        shader_input_attachment_array_dynamic_indexing,
+        shader_uniform_texel_buffer_array_dynamic_indexing,
+        shader_storage_texel_buffer_array_dynamic_indexing,
+        shader_uniform_buffer_array_non_uniform_indexing,
+        shader_sampled_image_array_non_uniform_indexing,
+        shader_storage_buffer_array_non_uniform_indexing,
+        shader_storage_image_array_non_uniform_indexing,
+        shader_input_attachment_array_non_uniform_indexing,
+        shader_uniform_texel_buffer_array_non_uniform_indexing,
+        shader_storage_texel_buffer_array_non_uniform_indexing,
+        descriptor_binding_uniform_buffer_update_after_bind,
+        descriptor_binding_sampled_image_update_after_bind,
+        descriptor_binding_storage_image_update_after_bind,
+        descriptor_binding_storage_buffer_update_after_bind,
+        descriptor_binding_uniform_texel_buffer_update_after_bind,
+        descriptor_binding_storage_texel_buffer_update_after_bind,
+        descriptor_binding_update_unused_while_pending,
+        descriptor_binding_partially_bound,
+        descriptor_binding_variable_descriptor_count,
+        runtime_descriptor_array,
+    }
+    PhysicalDeviceScalarBlockLayoutFeatures(ExtScalarBlockLayoutFn): API_VERSION_1_2 {
+        scalar_block_layout,
+    }
+    PhysicalDeviceImagelessFramebufferFeatures(KhrImagelessFramebufferFn): API_VERSION_1_2 {
+        imageless_framebuffer,
+    }
+    PhysicalDeviceUniformBufferStandardLayoutFeatures(KhrUniformBufferStandardLayoutFn): API_VERSION_1_2 {
+        uniform_buffer_standard_layout,
+    }
+    PhysicalDeviceShaderSubgroupExtendedTypesFeatures(KhrShaderSubgroupExtendedTypesFn): API_VERSION_1_2 {
+        shader_subgroup_extended_types,
+    }
+    PhysicalDeviceSeparateDepthStencilLayoutsFeatures(KhrSeparateDepthStencilLayoutsFn): API_VERSION_1_2 {
+        separate_depth_stencil_layouts,
+    }
+    PhysicalDeviceHostQueryResetFeatures(ExtHostQueryResetFn): API_VERSION_1_2 {
+        host_query_reset,
+    }
+    PhysicalDeviceTimelineSemaphoreFeatures(KhrTimelineSemaphoreFn): API_VERSION_1_2 {
+        timeline_semaphore,
+    }
+    PhysicalDeviceBufferDeviceAddressFeatures(KhrBufferDeviceAddressFn): API_VERSION_1_2 {
+        buffer_device_address,
+        buffer_device_address_capture_replay,
+        buffer_device_address_multi_device,
+    }
+    PhysicalDeviceVulkanMemoryModelFeatures(KhrVulkanMemoryModelFn): API_VERSION_1_2 {
+        vulkan_memory_model,
+        vulkan_memory_model_device_scope,
+        vulkan_memory_model_availability_visibility_chains,
+    }
+    PhysicalDeviceImageRobustnessFeatures(ExtImageRobustnessFn): API_VERSION_1_3 {
+        robust_image_access,
+    }
+    PhysicalDeviceInlineUniformBlockFeatures(ExtInlineUniformBlockFn): API_VERSION_1_3 {
+        inline_uniform_block,
+        descriptor_binding_inline_uniform_block_update_after_bind,
+    }
+    PhysicalDevicePipelineCreationCacheControlFeatures(ExtPipelineCreationCacheControlFn): API_VERSION_1_3 {
+        pipeline_creation_cache_control,
+    }
+    PhysicalDevicePrivateDataFeatures(ExtPrivateDataFn): API_VERSION_1_3 {
+        private_data,
+    }
+    PhysicalDeviceShaderDemoteToHelperInvocationFeatures(ExtShaderDemoteToHelperInvocationFn): API_VERSION_1_3 {
+        shader_demote_to_helper_invocation,
+    }
+    PhysicalDeviceShaderTerminateInvocationFeatures(KhrShaderTerminateInvocationFn): API_VERSION_1_3 {
+        shader_terminate_invocation,
+    }
+    PhysicalDeviceSubgroupSizeControlFeatures(ExtSubgroupSizeControlFn): API_VERSION_1_3 {
+        subgroup_size_control,
+        compute_full_subgroups,
+    }
+    PhysicalDeviceSynchronization2Features(KhrSynchronization2Fn): API_VERSION_1_3 {
+        synchronization2,
+    }
+    PhysicalDeviceTextureCompressionASTCHDRFeatures(ExtTextureCompressionAstcHdrFn): API_VERSION_1_3 {
+        texture_compression_astc_hdr,
+    }
+    PhysicalDeviceZeroInitializeWorkgroupMemoryFeatures(KhrZeroInitializeWorkgroupMemoryFn): API_VERSION_1_3 {
+        shader_zero_initialize_workgroup_memory,
+    }
+    PhysicalDeviceDynamicRenderingFeatures(KhrDynamicRenderingFn): API_VERSION_1_3 {
+        dynamic_rendering,
+    }
+    PhysicalDeviceShaderIntegerDotProductFeatures(KhrShaderIntegerDotProductFn): API_VERSION_1_3 {
+        shader_integer_dot_product,
+    }
+    PhysicalDeviceMaintenance4Features(KhrMaintenance4Fn): API_VERSION_1_3 {
+        maintenance4,
+    }
+    PhysicalDeviceAccelerationStructureFeaturesKHR(KhrAccelerationStructureFn) {
+        acceleration_structure,
+        acceleration_structure_capture_replay,
+        acceleration_structure_indirect_build,
+        acceleration_structure_host_commands,
+        descriptor_binding_acceleration_structure_update_after_bind,
+    }
+    PhysicalDeviceBufferDeviceAddressFeaturesEXT(ExtBufferDeviceAddressFn) {
+        buffer_device_address,
+        buffer_device_address_capture_replay,
+        buffer_device_address_multi_device,
+    }
+    PhysicalDeviceConditionalRenderingFeaturesEXT(ExtConditionalRenderingFn) {
+        conditional_rendering,
+        inherited_conditional_rendering,
+    }
+    PhysicalDeviceCustomBorderColorFeaturesEXT(ExtCustomBorderColorFn) {
+        custom_border_colors,
+        custom_border_color_without_format,
+    }
+    PhysicalDeviceDepthClipEnableFeaturesEXT(ExtDepthClipEnableFn) {
+        depth_clip_enable,
+    }
+    PhysicalDeviceDescriptorBufferFeaturesEXT(ExtDescriptorBufferFn) {
+        descriptor_buffer,
+        descriptor_buffer_capture_replay,
+        descriptor_buffer_image_layout_ignored,
+        descriptor_buffer_push_descriptors,
+    }
+    PhysicalDeviceExtendedDynamicStateFeaturesEXT(ExtExtendedDynamicStateFn) {
+        extended_dynamic_state,
+    }
+    PhysicalDeviceExtendedDynamicState2FeaturesEXT(ExtExtendedDynamicState2Fn) {
+        extended_dynamic_state2,
+        extended_dynamic_state2_logic_op,
+        extended_dynamic_state2_patch_control_points,
+    }
+    PhysicalDeviceExtendedDynamicState3FeaturesEXT(ExtExtendedDynamicState3Fn) {
+        extended_dynamic_state3_tessellation_domain_origin,
+        extended_dynamic_state3_depth_clamp_enable,
+        extended_dynamic_state3_polygon_mode,
+        extended_dynamic_state3_rasterization_samples,
+        extended_dynamic_state3_sample_mask,
+        extended_dynamic_state3_alpha_to_coverage_enable,
+        extended_dynamic_state3_alpha_to_one_enable,
+        extended_dynamic_state3_logic_op_enable,
+        extended_dynamic_state3_color_blend_enable,
+        extended_dynamic_state3_color_blend_equation,
+        extended_dynamic_state3_color_write_mask,
+        extended_dynamic_state3_rasterization_stream,
+        extended_dynamic_state3_conservative_rasterization_mode,
+        extended_dynamic_state3_extra_primitive_overestimation_size,
+        extended_dynamic_state3_depth_clip_enable,
+        extended_dynamic_state3_sample_locations_enable,
+        extended_dynamic_state3_color_blend_advanced,
+        extended_dynamic_state3_provoking_vertex_mode,
+        extended_dynamic_state3_line_rasterization_mode,
+        extended_dynamic_state3_line_stipple_enable,
+        extended_dynamic_state3_depth_clip_negative_one_to_one,
+        extended_dynamic_state3_viewport_w_scaling_enable,
+        extended_dynamic_state3_viewport_swizzle,
+        extended_dynamic_state3_coverage_to_color_enable,
+        extended_dynamic_state3_coverage_to_color_location,
+        extended_dynamic_state3_coverage_modulation_mode,
+        extended_dynamic_state3_coverage_modulation_table_enable,
+        extended_dynamic_state3_coverage_modulation_table,
+        extended_dynamic_state3_coverage_reduction_mode,
+        extended_dynamic_state3_representative_fragment_test_enable,
+        extended_dynamic_state3_shading_rate_image_enable,
+    }
+    PhysicalDeviceFragmentShaderInterlockFeaturesEXT(ExtFragmentShaderInterlockFn) {
+        fragment_shader_sample_interlock,
+        fragment_shader_pixel_interlock,
+        fragment_shader_shading_rate_interlock,
+    }
+    PhysicalDeviceFragmentShadingRateFeaturesKHR(KhrFragmentShadingRateFn) {
+        pipeline_fragment_shading_rate,
+        primitive_fragment_shading_rate,
+        attachment_fragment_shading_rate,
+    }
+    PhysicalDeviceIndexTypeUint8FeaturesEXT(ExtIndexTypeUint8Fn) {
+        index_type_uint8,
+    }
+    PhysicalDeviceLineRasterizationFeaturesEXT(ExtLineRasterizationFn) {
+        rectangular_lines,
+        bresenham_lines,
+        smooth_lines,
+        stippled_rectangular_lines,
+        stippled_bresenham_lines,
+        stippled_smooth_lines,
+    }
+    PhysicalDeviceMemoryPriorityFeaturesEXT(ExtMemoryPriorityFn) {
+        memory_priority,
+    }
+    PhysicalDeviceMeshShaderFeaturesEXT(ExtMeshShaderFn) {
+        task_shader,
+        mesh_shader,
+        multiview_mesh_shader,
+        primitive_fragment_shading_rate_mesh_shader,
+        mesh_shader_queries,
+    }
+    PhysicalDeviceMeshShaderFeaturesNV(NvMeshShaderFn) {
+        task_shader,
+        mesh_shader,
+    }
+    PhysicalDeviceMultiDrawFeaturesEXT(ExtMultiDrawFn) {
+        multi_draw,
+    }
+    PhysicalDevicePageableDeviceLocalMemoryFeaturesEXT(ExtPageableDeviceLocalMemoryFn) {
+        pageable_device_local_memory,
+    }
+    PhysicalDevicePipelineExecutablePropertiesFeaturesKHR(KhrPipelineExecutablePropertiesFn) {
+        pipeline_executable_info,
+    }
+    PhysicalDevicePresentIdFeaturesKHR(KhrPresentIdFn) {
+        present_id,
+    }
+    PhysicalDevicePresentWaitFeaturesKHR(KhrPresentWaitFn) {
+        present_wait,
+    }
+    PhysicalDeviceRayQueryFeaturesKHR(KhrRayQueryFn) {
+        ray_query,
+    }
+    PhysicalDeviceRayTracingMaintenance1FeaturesKHR(KhrRayTracingMaintenance1Fn) {
+        ray_tracing_maintenance1,
+        ray_tracing_pipeline_trace_rays_indirect2,
+    }
+    PhysicalDeviceRayTracingPipelineFeaturesKHR(KhrRayTracingPipelineFn) {
+        ray_tracing_pipeline,
+        ray_tracing_pipeline_shader_group_handle_capture_replay,
+        ray_tracing_pipeline_shader_group_handle_capture_replay_mixed,
+        ray_tracing_pipeline_trace_rays_indirect,
+        ray_traversal_primitive_culling,
+    }
+    PhysicalDeviceRobustness2FeaturesEXT(ExtRobustness2Fn) {
+        robust_buffer_access2,
+        robust_image_access2,
+        null_descriptor,
+    }
+    PhysicalDeviceShaderAtomicFloatFeaturesEXT(ExtShaderAtomicFloatFn) {
+        shader_buffer_float32_atomics,
+        shader_buffer_float32_atomic_add,
+        shader_buffer_float64_atomics,
+        shader_buffer_float64_atomic_add,
+        shader_shared_float32_atomics,
+        shader_shared_float32_atomic_add,
+        shader_shared_float64_atomics,
+        shader_shared_float64_atomic_add,
+        shader_image_float32_atomics,
+        shader_image_float32_atomic_add,
+        sparse_image_float32_atomics,
+        sparse_image_float32_atomic_add,
+    }
+    PhysicalDeviceShaderAtomicFloat2FeaturesEXT(ExtShaderAtomicFloat2Fn) {
+        shader_buffer_float16_atomics,
+        shader_buffer_float16_atomic_add,
+        shader_buffer_float16_atomic_min_max,
+        shader_buffer_float32_atomic_min_max,
+        shader_buffer_float64_atomic_min_max,
+        shader_shared_float16_atomics,
+        shader_shared_float16_atomic_add,
+        shader_shared_float16_atomic_min_max,
+        shader_shared_float32_atomic_min_max,
+        shader_shared_float64_atomic_min_max,
+        shader_image_float32_atomic_min_max,
+        sparse_image_float32_atomic_min_max,
+    }
+    PhysicalDeviceShaderClockFeaturesKHR(KhrShaderClockFn) {
+        shader_subgroup_clock,
+        shader_device_clock,
+    }
+    PhysicalDeviceShaderObjectFeaturesEXT(ExtShaderObjectFn) {
+        shader_object,
+    }
+    PhysicalDeviceTransformFeedbackFeaturesEXT(ExtTransformFeedbackFn) {
+        transform_feedback,
+        geometry_streams,
+    }
+    PhysicalDeviceVertexInputDynamicStateFeaturesEXT(ExtVertexInputDynamicStateFn) {
+        vertex_input_dynamic_state,
+    }
+}
+
+/// An owned copy of an extension feature struct.
+///
+/// Feature structs are an `sType`/`pNext` header followed only by `VkBool32`
+/// members, so they are stored as those members to keep structs of different
+/// types together and out of reach of dangling `pNext` pointers.
+#[derive(Clone, Debug)]
+pub(crate) struct ExtensionFeatures {
+    type_id: TypeId,
+    type_name: &'static str,
+    promoted: Option<u32>,
+    extension: Option<&'static CStr>,
+    names: Vec<&'static str>,
+    values: Vec<vk::Bool32>,
+    // Convert between the values and a buffer holding the struct, for chaining.
+    to_buffer: fn(&[vk::Bool32]) -> Vec<u64>,
+    from_buffer: fn(&[u64]) -> Vec<vk::Bool32>,
+}
+
+impl ExtensionFeatures {
+    pub(crate) fn new<T: ExtensionFeatureStruct>(features: &T) -> Self {
+        ExtensionFeatures {
+            type_id: TypeId::of::<T>(),
+            type_name: any::type_name::<T>(),
+            promoted: T::PROMOTED,
+            extension: T::EXTENSION,
+            names: T::FIELDS.iter().map(|field| field.name).collect(),
+            values: read_fields(features),
+            to_buffer: |values| {
+                let features = write_fields::<T>(values);
+                // u64 keeps the pointers in the header aligned.
+                let mut buffer = vec![0u64; mem::size_of::<T>().div_ceil(8)];
+                unsafe { ptr::write(buffer.as_mut_ptr() as *mut T, features) };
+                buffer
+            },
+            from_buffer: |buffer| read_fields(unsafe { &*(buffer.as_ptr() as *const T) }),
+        }
+    }
+
+    /// A copy of the struct with every feature turned off.
+    pub(crate) fn none(&self) -> Self {
+        ExtensionFeatures {
+            values: vec![vk::FALSE; self.values.len()],
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn struct_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Copy the values into a `T`, which must be the type this was made from.
+    pub(crate) fn to_struct<T: ExtensionFeatureStruct>(&self) -> T {
+        assert_eq!(self.type_id, TypeId::of::<T>());
+        write_fields(&self.values)
+    }
+
+    /// Names the required members that aren't available, as
+    /// `PhysicalDeviceMeshShaderFeaturesEXT::mesh_shader`.
+    pub(crate) fn missing(&self, available: &Self) -> Vec<String> {
+        let name = self.type_name.rsplit("::").next().unwrap_or(self.type_name);
+        self.names
+            .iter()
+            .zip(self.values.iter().zip(&available.values))
+            .filter(|(_, (required, available))| **required != 0 && **available == 0)
+            .map(|(field, _)| format!("{}::{}", name, field))
+            .collect()
+    }
+
+    /// Whether a device with `api_version` knows the struct, either from core
+    /// or from an extension for which `has_extension` is true.
+    pub(crate) fn is_known(&self, api_version: u32, has_extension: impl Fn(&CStr) -> bool) -> bool {
+        self.promoted
+            .is_some_and(|promoted| api_version >= promoted)
+            || self.extension.is_some_and(has_extension)
+    }
+
+    /// Whether the members are part of the per-version feature structs of
+    /// `api_version`. The struct can't be chained alongside those, which are
+    /// only chained from 1.2 on.
+    pub(crate) fn is_core(&self, api_version: u32) -> bool {
+        self.promoted
+            .is_some_and(|promoted| api_version >= promoted.max(vk::API_VERSION_1_2))
+    }
+
+    /// The set members as core features. Only meaningful for promoted structs,
+    /// whose member names match the per-version ones.
+    pub(crate) fn core_features(&self) -> Features {
+        self.names
+            .iter()
+            .zip(&self.values)
+            .filter(|(_, value)| **value != 0)
+            .map(|(name, _)| {
+                name.parse()
+                    .unwrap_or_else(|_| panic!("{} has no core feature {}", self.type_name, name))
+            })
+            .collect()
+    }
+
+    /// Enable every requested member that is also available.
+    pub(crate) fn enable(&mut self, available: &Self, requested: &Self) {
+        for (i, enabled) in self.values.iter_mut().enumerate() {
+            if requested.values[i] != 0 && available.values[i] != 0 {
+                *enabled = vk::TRUE;
+            }
+        }
+    }
+}
+
+fn read_fields<T: ExtensionFeatureStruct>(features: &T) -> Vec<vk::Bool32> {
+    T::FIELDS
+        .iter()
+        .map(|field| (field.get)(features))
+        .collect()
+}
+
+fn write_fields<T: ExtensionFeatureStruct>(values: &[vk::Bool32]) -> T {
+    let mut features = T::default();
+    for (field, value) in T::FIELDS.iter().zip(values) {
+        (field.set)(&mut features, *value);
+    }
+    features
+}
+
+/// Extension feature structs laid out as a `pNext` chain.
+pub(crate) struct FeatureChain {
+    buffers: Vec<Vec<u64>>,
+}
+
+impl FeatureChain {
+    pub(crate) fn new(features: &[ExtensionFeatures]) -> Self {
+        let mut buffers: Vec<Vec<u64>> = features
+            .iter()
+            .map(|features| (features.to_buffer)(&features.values))
+            .collect();
+        for i in 1..buffers.len() {
+            let next = buffers[i].as_mut_ptr() as *mut vk::BaseOutStructure;
+            unsafe { (*(buffers[i - 1].as_mut_ptr() as *mut vk::BaseOutStructure)).p_next = next };
+        }
+        FeatureChain { buffers }
+    }
+
+    /// Put the chain in front of `p_next`, returning the new head. The chain
+    /// must outlive any use of the result.
+    pub(crate) fn prepend(&mut self, p_next: *mut c_void) -> *mut c_void {
+        let Some(tail) = self.buffers.last_mut() else {
+            return p_next;
+        };
+        unsafe { (*(tail.as_mut_ptr() as *mut vk::BaseOutStructure)).p_next = p_next as _ };
+        self.buffers[0].as_mut_ptr() as *mut c_void
+    }
+
+    /// Copy the values a query wrote into the chain back out.
+    pub(crate) fn read(&self, features: &mut [ExtensionFeatures]) {
+        for (features, buffer) in features.iter_mut().zip(&self.buffers) {
+            features.values = (features.from_buffer)(buffer);
+        }
+    }
+}
+
+/// Fill in which members of each struct the device supports. They stay off if
+/// `vkGetPhysicalDeviceFeatures2` isn't available, and for structs the device
+/// doesn't know, which may not be queried.
+pub(crate) unsafe fn query_extension_features(
+    instance: &Instance,
+    info: &PhysicalDeviceInfo,
+    features: &mut [ExtensionFeatures],
+) {
+    let api_version = info.properties().api_version.min(instance.api_version());
+    let mut known: Vec<ExtensionFeatures> = features
+        .iter()
+        .filter(|features| features.is_known(api_version, |name| info.supports_extension(name)))
+        .cloned()
+        .collect();
+    if known.is_empty() {
+        return;
+    }
+    let mut chain = FeatureChain::new(&known);
+    let mut features2 = vk::PhysicalDeviceFeatures2 {
+        p_next: chain.prepend(ptr::null_mut()),
+        ..Default::default()
+    };
+    if instance.get_physical_device_features2(info.physical_device(), &mut features2) {
+        chain.read(&mut known);
+        for features in features.iter_mut() {
+            if let Some(queried) = known.iter().find(|f| f.type_id == features.type_id) {
+                features.values.clone_from(&queried.values);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_extension_features_are_named() {
+        let required = ExtensionFeatures::new(&vk::PhysicalDeviceMeshShaderFeaturesEXT {
+            task_shader: vk::TRUE,
+            mesh_shader: vk::TRUE,
+            ..Default::default()
+        });
+        let available = ExtensionFeatures::new(&vk::PhysicalDeviceMeshShaderFeaturesEXT {
+            mesh_shader: vk::TRUE,
+            primitive_fragment_shading_rate_mesh_shader: vk::TRUE,
+            ..Default::default()
+        });
+        assert_eq!(
+            required.missing(&available),
+            ["PhysicalDeviceMeshShaderFeaturesEXT::task_shader"]
+        );
+        assert!(available.missing(&available).is_empty());
+
+        // Robustness2 has padding after its three members.
+        let robustness = ExtensionFeatures::new(&vk::PhysicalDeviceRobustness2FeaturesEXT {
+            null_descriptor: vk::TRUE,
+            ..Default::default()
+        });
+        assert_eq!(
+            robustness.missing(&robustness.none()),
+            ["PhysicalDeviceRobustness2FeaturesEXT::null_descriptor"]
+        );
+        let features: vk::PhysicalDeviceRobustness2FeaturesEXT = robustness.to_struct();
+        assert_eq!(features.null_descriptor, vk::TRUE);
+        assert_eq!(features.robust_buffer_access2, vk::FALSE);
+
+        // The chain holds the whole struct, header included.
+        let mut chain = FeatureChain::new(std::slice::from_ref(&robustness));
+        let head = chain.prepend(ptr::null_mut());
+        let chained = unsafe { &*(head as *const vk::PhysicalDeviceRobustness2FeaturesEXT) };
+        assert_eq!(
+            chained.s_type,
+            vk::StructureType::PHYSICAL_DEVICE_ROBUSTNESS_2_FEATURES_EXT
+        );
+        assert_eq!(chained.null_descriptor, vk::TRUE);
+        let mut read = [robustness.none()];
+        chain.read(&mut read);
+        assert_eq!(read[0].values, robustness.values);
+    }
+
+    #[test]
+    fn promoted_members_are_core_features() {
+        fn check<T: ExtensionFeatureStruct>() {
+            if T::PROMOTED.is_some() {
+                for field in T::FIELDS {
+                    assert!(
+                        field.name.parse::<Feature>().is_ok(),
+                        "{}::{}",
+                        any::type_name::<T>(),
+                        field.name
+                    );
+                }
+            }
+        }
+        check::<vk::PhysicalDevice16BitStorageFeatures>();
+        check::<vk::PhysicalDeviceShaderDrawParametersFeatures>();
+        check::<vk::PhysicalDeviceDescriptorIndexingFeatures>();
+        check::<vk::PhysicalDeviceBufferDeviceAddressFeatures>();
+        check::<vk::PhysicalDeviceInlineUniformBlockFeatures>();
+        check::<vk::PhysicalDeviceTextureCompressionASTCHDRFeatures>();
+
+        let timeline = ExtensionFeatures::new(&vk::PhysicalDeviceTimelineSemaphoreFeatures {
+            timeline_semaphore: vk::TRUE,
+            ..Default::default()
+        });
+        assert!(!timeline.is_core(vk::API_VERSION_1_1));
+        assert!(timeline.is_core(vk::API_VERSION_1_2));
+        assert_eq!(
+            timeline.core_features().iter().collect::<Vec<_>>(),
+            ["timeline_semaphore".parse::<Feature>().unwrap()]
+        );
+        let multiview = ExtensionFeatures::new(&vk::PhysicalDeviceMultiviewFeatures::default());
+        assert!(!multiview.is_core(vk::API_VERSION_1_1));
+        assert!(multiview.is_core(vk::API_VERSION_1_2));
+        let mesh = ExtensionFeatures::new(&vk::PhysicalDeviceMeshShaderFeaturesEXT::default());
+        assert!(!mesh.is_core(vk::API_VERSION_1_3));
+    }
+
+    #[test]
+    fn structs_are_known_from_core_or_extensions() {
+        let timeline =
+            ExtensionFeatures::new(&vk::PhysicalDeviceTimelineSemaphoreFeatures::default());
+        let mesh = ExtensionFeatures::new(&vk::PhysicalDeviceMeshShaderFeaturesEXT::default());
+        let protected =
+            ExtensionFeatures::new(&vk::PhysicalDeviceProtectedMemoryFeatures::default());
+        let has_timeline = |name: &CStr| name == vk::KhrTimelineSemaphoreFn::name();
+        let has_none = |_: &CStr| false;
+
+        assert!(timeline.is_known(vk::API_VERSION_1_1, has_timeline));
+        assert!(!timeline.is_known(vk::API_VERSION_1_1, has_none));
+        assert!(timeline.is_known(vk::API_VERSION_1_2, has_none));
+        assert!(!mesh.is_known(vk::API_VERSION_1_3, has_timeline));
+        assert!(mesh.is_known(vk::API_VERSION_1_0, |name| {
+            name == vk::ExtMeshShaderFn::name()
+        }));
+        assert!(!protected.is_known(vk::API_VERSION_1_0, |_| true));
+        assert!(protected.is_known(vk::API_VERSION_1_1, has_none));
+    }
+
+    #[test]
+    fn feature_names_round_trip() {
+        for feature in Feature::all() {
+            assert_eq!(feature.name().parse::<Feature>().unwrap(), feature);
+        }
+        assert!("not_a_feature".parse::<Feature>().is_err());
+    }
+}
@@ -3,50 +3,102 @@ use std::{
     collections::HashMap,
     ffi::{c_void, CStr, CString},
     os::raw::c_char,
+    ptr,
     sync::Arc,
 };
 
-use ash::{vk, Entry};
+use ash::{extensions::khr::GetPhysicalDeviceProperties2, vk, Entry};
+
+use crate::{
+    debug::debug_callback_trampoline,
+    extensions::{contains_name, is_promoted, with_core_entry, Dependencies},
+    util::{debug, streq, warn, without_patch},
+    validation::{
+        LayerSetting, LayerSettingEXT, LayerSettingsCreateInfoEXT, LAYER_SETTINGS_EXTENSION_NAME,
+    },
+    DebugCallback, DebugMessage, DeviceExtension, Error, InstanceExtension,
+    InstanceExtensionLoader, LayerSettingValue, PhysicalDeviceInfo, ValidationCheck,
+    ValidationFeature,
+};
 
-use crate::{util::streq, Error, InstanceExtension, InstanceExtensionLoader};
+const VALIDATION_LAYER_NAME: &CStr = c"VK_LAYER_KHRONOS_validation";
 
 pub struct Instance {
     entry: Entry,
     instance: ash::Instance,
-    loaded_extensions: HashMap<TypeId, Box<dyn Any + 'static>>,
+    api_version: u32,
+    loaded_extensions: HashMap<TypeId, Box<dyn Any + Send + Sync + 'static>>,
+    enabled_extensions: Vec<CString>,
+    enabled_layers: Vec<CString>,
+    // The vkGetPhysicalDevice*2 functions, from core or the KHR extension.
+    properties2: Option<GetPhysicalDeviceProperties2>,
+    debug_messenger: vk::DebugUtilsMessengerEXT,
+    // Referenced by the messenger through `p_user_data`, so it must outlive the instance.
+    _debug_callback: Option<Box<DebugCallback>>,
 }
 
 pub struct InstanceBuilder<'a> {
-    api_version: u32,
+    minimum_version: u32,
+    desired_version: u32,
     app_name: &'a str,
     engine_name: &'a str,
     app_version: u32,
     engine_version: u32,
     required_extensions: Vec<(*const c_char, InstanceExtensionLoader)>,
     optional_extensions: Vec<(*const c_char, InstanceExtensionLoader)>,
-    enabled_layers: Vec<*const c_char>,
+    required_layers: Vec<CString>,
+    optional_layers: Vec<CString>,
     debug_messenger_fn: vk::PFN_vkDebugUtilsMessengerCallbackEXT,
+    debug_callback: Option<Box<DebugCallback>>,
+    debug_message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    debug_message_types: vk::DebugUtilsMessageTypeFlagsEXT,
+    enabled_validation_features: Vec<ValidationFeature>,
+    disabled_validation_checks: Vec<ValidationCheck>,
+    layer_settings: Vec<LayerSetting>,
+    device_extension_dependencies: Vec<*const c_char>,
     is_headless: bool,
 }
 
 impl<'a> InstanceBuilder<'a> {
     pub fn new() -> Self {
         InstanceBuilder {
-            api_version: vk::API_VERSION_1_0,
+            minimum_version: vk::API_VERSION_1_0,
+            desired_version: vk::API_VERSION_1_0,
             app_name: "unspecified",
             engine_name: "unspecified",
             app_version: 0,
             engine_version: 0,
             required_extensions: Vec::default(),
             optional_extensions: Vec::default(),
-            enabled_layers: Vec::default(),
+            required_layers: Vec::default(),
+            optional_layers: Vec::default(),
             debug_messenger_fn: None,
+            debug_callback: None,
+            debug_message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::empty(),
+            debug_message_types: vk::DebugUtilsMessageTypeFlagsEXT::empty(),
+            enabled_validation_features: Vec::default(),
+            disabled_validation_checks: Vec::default(),
+            layer_settings: Vec::default(),
+            device_extension_dependencies: Vec::default(),
             is_headless: false,
         }
     }
 
-    pub fn api_version(mut self, version: u32) -> Self {
-        self.api_version = version;
+    /// Same as [`desired_api_version`](Self::desired_api_version). Use
+    /// [`minimum_instance_version`](Self::minimum_instance_version) to require it.
+    pub fn api_version(self, version: u32) -> Self {
+        self.desired_api_version(version)
+    }
+
+    /// Fail instance creation if the loader doesn't support at least this version.
+    pub fn minimum_instance_version(mut self, version: u32) -> Self {
+        self.minimum_version = version;
+        self
+    }
+
+    /// Use up to this API version if the loader supports it.
+    pub fn desired_api_version(mut self, version: u32) -> Self {
+        self.desired_version = version;
         self
     }
 
@@ -104,14 +156,112 @@ impl<'a> InstanceBuilder<'a> {
         self
     }
 
+    /// Enable the instance extensions that the device extension `E` depends on,
+    /// so it can be required from a [`DeviceBuilder`](crate::DeviceBuilder) later.
+    pub fn enable_dependencies_of<E: DeviceExtension + 'static>(mut self) -> Self {
+        if !self.device_extension_dependencies.contains(&E::name()) {
+            self.device_extension_dependencies.push(E::name());
+        }
+        self
+    }
+
+    /// Forward all debug messages to the `log` (or `tracing`) crate.
     pub fn use_default_debug_messenger(mut self) -> Self {
         self.debug_messenger_fn = Some(default_debug_message_func);
+        self.debug_callback = None;
+        self.debug_message_severity = vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+            | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+            | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+            | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE;
+        self.debug_message_types = vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+            | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
+            | vk::DebugUtilsMessageTypeFlagsEXT::GENERAL;
         self.require_extension::<ash::extensions::ext::DebugUtils>()
     }
 
-    pub fn request_validation_layers(mut self) -> Self {
-        self.enabled_layers
-            .push(b"VK_LAYER_KHRONOS_validation\0".as_ptr() as *const c_char);
+    /// Forward debug messages matching `severity` and `types` to `callback`.
+    ///
+    /// The callback is owned by the [`Instance`] and may be called from any
+    /// thread that makes Vulkan calls. Panics inside the callback are caught.
+    pub fn debug_messenger<F>(
+        mut self,
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        types: vk::DebugUtilsMessageTypeFlagsEXT,
+        callback: F,
+    ) -> Self
+    where
+        F: Fn(
+                vk::DebugUtilsMessageSeverityFlagsEXT,
+                vk::DebugUtilsMessageTypeFlagsEXT,
+                &DebugMessage,
+            ) + Send
+            + Sync
+            + 'static,
+    {
+        self.debug_messenger_fn = Some(debug_callback_trampoline);
+        self.debug_callback = Some(Box::new(Box::new(callback)));
+        self.debug_message_severity = severity;
+        self.debug_message_types = types;
+        self.require_extension::<ash::extensions::ext::DebugUtils>()
+    }
+
+    /// Enable a layer, failing instance creation if it isn't installed.
+    pub fn require_layer(mut self, name: &CStr) -> Self {
+        let name = name.to_owned();
+        self.optional_layers.retain(|layer| *layer != name);
+        if !self.required_layers.contains(&name) {
+            self.required_layers.push(name);
+        }
+        self
+    }
+
+    /// Enable a layer if it is installed.
+    pub fn optional_layer(mut self, name: &CStr) -> Self {
+        let name = name.to_owned();
+        if !self.required_layers.contains(&name) && !self.optional_layers.contains(&name) {
+            self.optional_layers.push(name);
+        }
+        self
+    }
+
+    /// Enable the Khronos validation layer if it is installed.
+    pub fn request_validation_layers(self) -> Self {
+        self.optional_layer(VALIDATION_LAYER_NAME)
+    }
+
+    /// Turn on an extra validation feature. Only takes effect when the
+    /// validation layer is enabled and supports `VK_EXT_validation_features`.
+    pub fn enable_validation_feature(mut self, feature: ValidationFeature) -> Self {
+        if !self.enabled_validation_features.contains(&feature) {
+            self.enabled_validation_features.push(feature);
+        }
+        self
+    }
+
+    /// Turn off a group of validation checks. Only takes effect when the
+    /// validation layer is enabled and supports `VK_EXT_validation_features`.
+    pub fn disable_validation_check(mut self, check: ValidationCheck) -> Self {
+        if !self.disabled_validation_checks.contains(&check) {
+            self.disabled_validation_checks.push(check);
+        }
+        self
+    }
+
+    /// Configure a layer through `VK_EXT_layer_settings`, e.g.
+    /// `layer_setting(c"VK_LAYER_KHRONOS_validation", c"duplicate_message_limit", 3u32)`.
+    ///
+    /// Settings for layers that aren't enabled or don't support the extension
+    /// are ignored.
+    pub fn layer_setting(
+        mut self,
+        layer: &CStr,
+        name: &CStr,
+        value: impl Into<LayerSettingValue>,
+    ) -> Self {
+        let setting = LayerSetting::new(layer, name, value.into());
+        self.layer_settings
+            .retain(|s| s.layer() != setting.layer() || s.name() != setting.name());
+        self.layer_settings.push(setting);
         self
     }
 
@@ -125,87 +275,301 @@ impl<'a> InstanceBuilder<'a> {
 
             let entry = Entry::load()?;
 
+            let api_version = self.negotiate_api_version(&entry)?;
+
+            let enabled_layers = self.select_layers(&entry)?;
+
+            let validation_enabled = enabled_layers
+                .iter()
+                .any(|layer| layer.as_c_str() == VALIDATION_LAYER_NAME);
+            let wants_validation_features = validation_enabled
+                && !(self.enabled_validation_features.is_empty()
+                    && self.disabled_validation_checks.is_empty());
+
             let mut requested_extensions: Vec<*const c_char> = Vec::new();
+            let mut use_validation_features = false;
+            let mut layer_settings: Vec<LayerSettingEXT> = Vec::new();
             // Check supported extensions. If there are no optional extensions,
             // we can skip querying extension support and just let instance
             // creation fail.
-            if !self.optional_extensions.is_empty() {
-                let extensions = entry.enumerate_instance_extension_properties(None)?;
+            if !self.optional_extensions.is_empty()
+                || wants_validation_features
+                || !self.layer_settings.is_empty()
+            {
+                let mut extensions = entry.enumerate_instance_extension_properties(None)?;
+                // Layers may provide extensions of their own.
+                let mut settings_layers: Vec<&CStr> = Vec::new();
+                for layer in &enabled_layers {
+                    let layer_extensions =
+                        entry.enumerate_instance_extension_properties(Some(layer))?;
+                    if layer_extensions.iter().any(|extension| {
+                        streq(
+                            extension.extension_name.as_ptr(),
+                            LAYER_SETTINGS_EXTENSION_NAME.as_ptr(),
+                        )
+                    }) {
+                        settings_layers.push(layer);
+                    }
+                    extensions.extend(layer_extensions);
+                }
+                let is_available = |name: *const c_char| {
+                    extensions
+                        .iter()
+                        .any(|extension| streq(name, extension.extension_name.as_ptr()))
+                };
+
+                // Optional extensions are only enabled if all of their
+                // dependencies are available too.
                 for (name, _) in &self.optional_extensions {
-                    for extension in &extensions {
-                        if streq(*name, extension.extension_name.as_ptr()) {
-                            requested_extensions.push(*name);
-                            break;
+                    if is_promoted(*name, api_version) || !is_available(*name) {
+                        continue;
+                    }
+                    let deps = Dependencies::of(*name, api_version, api_version).instance;
+                    if deps.iter().all(|dep| is_available(*dep)) {
+                        requested_extensions.push(*name);
+                        for dep in deps {
+                            if !contains_name(&requested_extensions, dep) {
+                                requested_extensions.push(dep);
+                            }
                         }
+                    } else {
+                        debug!(
+                            "Dependencies of optional extension {:?} are missing, skipping.",
+                            CStr::from_ptr(*name)
+                        );
+                    }
+                }
+
+                if wants_validation_features {
+                    let name = vk::ExtValidationFeaturesFn::name().as_ptr();
+                    if is_available(name) {
+                        requested_extensions.push(name);
+                        use_validation_features = true;
+                    } else {
+                        warn!("VK_EXT_validation_features is not supported, ignoring validation features.");
+                    }
+                }
+
+                // Settings are only passed to layers that understand them.
+                for setting in &self.layer_settings {
+                    if settings_layers.contains(&setting.layer()) {
+                        layer_settings.push(setting.to_raw());
+                    } else {
+                        debug!(
+                            "Layer {:?} doesn't support VK_EXT_layer_settings, ignoring {:?}.",
+                            setting.layer(),
+                            setting.name()
+                        );
                     }
                 }
+                if !layer_settings.is_empty() {
+                    requested_extensions.push(LAYER_SETTINGS_EXTENSION_NAME.as_ptr());
+                }
             }
 
-            // Add the required extensions
+            // Add the required extensions and everything they depend on, unless
+            // they're already part of core.
             for (name, _) in &self.required_extensions {
-                requested_extensions.push(*name);
+                if is_promoted(*name, api_version) {
+                    continue;
+                }
+                if !contains_name(&requested_extensions, *name) {
+                    requested_extensions.push(*name);
+                }
+                for dep in Dependencies::of(*name, api_version, api_version).instance {
+                    if !contains_name(&requested_extensions, dep) {
+                        requested_extensions.push(dep);
+                    }
+                }
+            }
+            // The device's API version isn't known yet, so assume the worst.
+            for name in &self.device_extension_dependencies {
+                for dep in Dependencies::of(*name, vk::API_VERSION_1_0, api_version).instance {
+                    if !contains_name(&requested_extensions, dep) {
+                        requested_extensions.push(dep);
+                    }
+                }
             }
 
             let app_name = CString::new(self.app_name).unwrap();
             let engine_name = CString::new(self.engine_name).unwrap();
 
             let app_info = vk::ApplicationInfo::builder()
-                .api_version(self.api_version)
+                .api_version(api_version)
                 .application_name(app_name.as_c_str())
                 .application_version(self.app_version)
                 .engine_name(engine_name.as_c_str())
                 .engine_version(self.engine_version);
 
-            let create_info = vk::InstanceCreateInfo::builder()
+            let layer_names: Vec<*const c_char> =
+                enabled_layers.iter().map(|layer| layer.as_ptr()).collect();
+            let mut create_info = vk::InstanceCreateInfo::builder()
                 .application_info(&app_info)
                 .enabled_extension_names(&requested_extensions)
-                .enabled_layer_names(&self.enabled_layers);
-
-            let instance = match self.debug_messenger_fn {
-                Some(func) => {
-                    let mut debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
-                        .message_severity(
-                            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-                                | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                                | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
-                                | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
-                        )
-                        .message_type(
-                            vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
-                                | vk::DebugUtilsMessageTypeFlagsEXT::GENERAL,
-                        )
-                        .pfn_user_callback(Some(func));
-                    let create_info = create_info.push_next(&mut debug_info);
+                .enabled_layer_names(&layer_names);
 
-                    entry.create_instance(&create_info, None)?
-                }
-                None => entry.create_instance(&create_info, None)?,
-            };
+            let enabled_validation_features: Vec<vk::ValidationFeatureEnableEXT> = self
+                .enabled_validation_features
+                .iter()
+                .map(|feature| feature.to_vk())
+                .collect();
+            let disabled_validation_features: Vec<vk::ValidationFeatureDisableEXT> = self
+                .disabled_validation_checks
+                .iter()
+                .map(|check| check.to_vk())
+                .collect();
+            let mut validation_features = vk::ValidationFeaturesEXT::builder()
+                .enabled_validation_features(&enabled_validation_features)
+                .disabled_validation_features(&disabled_validation_features);
+            if use_validation_features {
+                create_info = create_info.push_next(&mut validation_features);
+            }
+
+            let mut layer_settings_info = LayerSettingsCreateInfoEXT::new(&layer_settings);
+            if !layer_settings.is_empty() {
+                create_info = create_info.push_next(&mut layer_settings_info);
+            }
 
-            let mut loaded_extensions: HashMap<TypeId, Box<dyn Any + 'static>> = HashMap::new();
-            for (name, loader) in self.optional_extensions {
-                if requested_extensions.contains(&name) {
-                    let ext = loader(&entry, &instance);
+            let user_data = self
+                .debug_callback
+                .as_deref()
+                .map_or(ptr::null_mut(), |callback| {
+                    callback as *const DebugCallback as *mut c_void
+                });
+            let mut debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
+                .message_severity(self.debug_message_severity)
+                .message_type(self.debug_message_types)
+                .pfn_user_callback(self.debug_messenger_fn)
+                .user_data(user_data);
+
+            // Chaining the messenger info also covers instance creation and
+            // destruction, which the persistent messenger can't see.
+            if self.debug_messenger_fn.is_some() {
+                create_info = create_info.push_next(&mut debug_info);
+            }
+            let instance = entry.create_instance(&create_info, None)?;
+
+            let mut loaded_extensions: HashMap<TypeId, Box<dyn Any + Send + Sync + 'static>> =
+                HashMap::new();
+            let properties2 = with_core_entry(&entry, |core_entry| {
+                let loader_entry = |name: *const c_char| {
+                    if requested_extensions.contains(&name) {
+                        &entry
+                    } else {
+                        core_entry
+                    }
+                };
+                for (name, loader) in self.optional_extensions {
+                    if requested_extensions.contains(&name) || is_promoted(name, api_version) {
+                        let ext = loader(loader_entry(name), &instance);
+                        let id = ext.as_ref().type_id();
+                        loaded_extensions.insert(id, ext);
+                    }
+                }
+                for (name, loader) in self.required_extensions {
+                    let ext = loader(loader_entry(name), &instance);
                     let id = ext.as_ref().type_id();
                     loaded_extensions.insert(id, ext);
                 }
-            }
 
-            for (_, loader) in self.required_extensions {
-                let ext = loader(&entry, &instance);
-                let id = ext.as_ref().type_id();
-                loaded_extensions.insert(id, ext);
-            }
+                let properties2 = GetPhysicalDeviceProperties2::name().as_ptr();
+                if api_version >= vk::API_VERSION_1_1 {
+                    Some(GetPhysicalDeviceProperties2::new(core_entry, &instance))
+                } else if contains_name(&requested_extensions, properties2) {
+                    Some(GetPhysicalDeviceProperties2::new(&entry, &instance))
+                } else {
+                    None
+                }
+            });
+
+            let debug_messenger = match self.debug_messenger_fn {
+                Some(_) => {
+                    let debug_utils = loaded_extensions
+                        .get(&TypeId::of::<ash::extensions::ext::DebugUtils>())
+                        .and_then(|e| e.downcast_ref::<ash::extensions::ext::DebugUtils>())
+                        .unwrap();
+                    // The chained info points into the instance create chain,
+                    // but the messenger's info must not have a pNext.
+                    let messenger_info = vk::DebugUtilsMessengerCreateInfoEXT {
+                        p_next: ptr::null(),
+                        ..*debug_info
+                    };
+                    match debug_utils.create_debug_utils_messenger(&messenger_info, None) {
+                        Ok(messenger) => messenger,
+                        Err(err) => {
+                            instance.destroy_instance(None);
+                            return Err(err.into());
+                        }
+                    }
+                }
+                None => vk::DebugUtilsMessengerEXT::null(),
+            };
 
             Ok(Arc::new(Instance {
                 entry,
                 instance,
+                api_version,
                 loaded_extensions,
+                enabled_extensions: requested_extensions
+                    .iter()
+                    .map(|name| CStr::from_ptr(*name).to_owned())
+                    .collect(),
+                enabled_layers,
+                properties2,
+                debug_messenger,
+                _debug_callback: self.debug_callback,
             }))
         }
     }
 
+    fn negotiate_api_version(&self, entry: &Entry) -> Result<u32, Error> {
+        // Vulkan 1.0 loaders don't have vkEnumerateInstanceVersion.
+        let available = entry
+            .try_enumerate_instance_version()?
+            .map_or(vk::API_VERSION_1_0, without_patch);
+        let minimum = without_patch(self.minimum_version);
+        if available < minimum {
+            return Err(Error::InstanceVersionTooLow {
+                required: minimum,
+                available,
+            });
+        }
+
+        Ok(without_patch(self.desired_version)
+            .min(available)
+            .max(minimum))
+    }
+
+    unsafe fn select_layers(&self, entry: &Entry) -> Result<Vec<CString>, Error> {
+        if self.required_layers.is_empty() && self.optional_layers.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let available = entry.enumerate_instance_layer_properties()?;
+        let is_available = |name: &CStr| {
+            available
+                .iter()
+                .any(|layer| streq(layer.layer_name.as_ptr(), name.as_ptr()))
+        };
+
+        let mut layers = Vec::new();
+        for layer in &self.required_layers {
+            if !is_available(layer) {
+                return Err(Error::LayerNotPresent(layer.to_string_lossy().into_owned()));
+            }
+            layers.push(layer.clone());
+        }
+        for layer in &self.optional_layers {
+            if is_available(layer) {
+                layers.push(layer.clone());
+            } else {
+                warn!("Optional layer {:?} is not present, skipping.", layer);
+            }
+        }
+
+        Ok(layers)
+    }
+
     fn require_surface_extensions(mut self) -> Self {
         self = self.require_extension::<ash::extensions::khr::Surface>();
         if cfg!(target_os = "windows") {
@@ -241,6 +605,11 @@ impl Instance {
         &self.instance
     }
 
+    /// The API version the instance was created with.
+    pub fn api_version(&self) -> u32 {
+        self.api_version
+    }
+
     pub fn extension<E: InstanceExtension + 'static>(&self) -> Option<&E> {
         let id = TypeId::of::<E>();
         self.loaded_extensions
@@ -248,6 +617,93 @@ impl Instance {
             .map(|e| e.downcast_ref::<E>().unwrap())
     }
 
+    /// The extensions that were enabled. Extensions provided by the API
+    /// version aren't listed.
+    pub fn enabled_extensions(&self) -> &[CString] {
+        &self.enabled_extensions
+    }
+
+    /// Whether the extension was enabled or is provided by the instance's API version.
+    pub fn is_extension_enabled(&self, name: &CStr) -> bool {
+        let promoted = unsafe { is_promoted(name.as_ptr(), self.api_version) };
+        promoted || self.enabled_extensions.iter().any(|ext| **ext == *name)
+    }
+
+    /// Run `query` with the vkGetPhysicalDevice*2 functions, from core or the
+    /// KHR extension as available. Returns false if neither is.
+    fn query_properties2(&self, query: impl FnOnce(&GetPhysicalDeviceProperties2)) -> bool {
+        match &self.properties2 {
+            Some(properties2) => {
+                query(properties2);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Query features through vkGetPhysicalDeviceFeatures2. Returns false if
+    /// it isn't available.
+    pub(crate) unsafe fn get_physical_device_features2(
+        &self,
+        physical_device: vk::PhysicalDevice,
+        features: &mut vk::PhysicalDeviceFeatures2,
+    ) -> bool {
+        self.query_properties2(|properties2| {
+            properties2.get_physical_device_features2(physical_device, features)
+        })
+    }
+
+    /// Query properties through vkGetPhysicalDeviceProperties2. Returns false
+    /// if it isn't available.
+    pub(crate) unsafe fn get_physical_device_properties2(
+        &self,
+        physical_device: vk::PhysicalDevice,
+        properties: &mut vk::PhysicalDeviceProperties2,
+    ) -> bool {
+        self.query_properties2(|properties2| {
+            properties2.get_physical_device_properties2(physical_device, properties)
+        })
+    }
+
+    /// Query memory properties through vkGetPhysicalDeviceMemoryProperties2.
+    /// Returns false if it isn't available.
+    pub(crate) unsafe fn get_physical_device_memory_properties2(
+        &self,
+        physical_device: vk::PhysicalDevice,
+        properties: &mut vk::PhysicalDeviceMemoryProperties2,
+    ) -> bool {
+        self.query_properties2(|properties2| {
+            properties2.get_physical_device_memory_properties2(physical_device, properties)
+        })
+    }
+
+    /// Every physical device in `vkEnumeratePhysicalDevices` order, for listing
+    /// them or picking one with [`PreferredDevice::Uuid`](crate::PreferredDevice::Uuid).
+    pub fn physical_devices(&self) -> Result<Vec<PhysicalDeviceInfo>, Error> {
+        unsafe {
+            self.instance
+                .enumerate_physical_devices()?
+                .into_iter()
+                .enumerate()
+                .map(|(index, pd)| PhysicalDeviceInfo::query(self, index as u32, pd))
+                .collect()
+        }
+    }
+
+    /// The layers that were actually enabled.
+    pub fn enabled_layers(&self) -> &[CString] {
+        &self.enabled_layers
+    }
+
+    pub fn is_layer_enabled(&self, name: &CStr) -> bool {
+        self.enabled_layers.iter().any(|layer| **layer == *name)
+    }
+
+    /// The messenger installed by the builder, or a null handle if none was requested.
+    pub fn debug_messenger(&self) -> vk::DebugUtilsMessengerEXT {
+        self.debug_messenger
+    }
+
     #[cfg(feature = "window")]
     pub fn create_surface<W: raw_window_handle::HasRawWindowHandle>(
         &self,
@@ -306,6 +762,11 @@ impl Instance {
 impl Drop for Instance {
     fn drop(&mut self) {
         unsafe {
+            if self.debug_messenger != vk::DebugUtilsMessengerEXT::null() {
+                self.extension::<ash::extensions::ext::DebugUtils>()
+                    .unwrap()
+                    .destroy_debug_utils_messenger(self.debug_messenger, None);
+            }
             self.instance.destroy_instance(None);
         }
     }
@@ -331,5 +792,6 @@ unsafe extern "system" fn default_debug_message_func(
         }
         _ => trace!(target: "vulkan", "[{:?}]: {}", message_types, msg),
     };
-    vk::Bool32::from(true)
+    // Returning true would abort the call that triggered the message.
+    vk::FALSE
 }
@@ -1,20 +1,34 @@
+mod debug;
 mod device;
 mod error;
 mod extensions;
+mod features;
 mod instance;
+mod physical_device;
+mod queue;
+mod report;
 mod swapchain;
 pub(crate) mod util;
+mod validation;
 
+pub use debug::{DebugCallback, DebugMessage, DebugObject};
 pub use device::Device;
 pub use device::DeviceBuilder;
+pub use device::DeviceScorer;
+pub use device::HeapBudget;
 pub use device::PreferredDevice;
 pub use error::Error;
 pub use extensions::DeviceExtension;
 pub use extensions::DeviceExtensionLoader;
 pub use extensions::InstanceExtension;
 pub use extensions::InstanceExtensionLoader;
+pub use features::{ExtensionFeatureStruct, Feature, Features};
 pub use instance::{Instance, InstanceBuilder};
+pub use physical_device::PhysicalDeviceInfo;
+pub use queue::{PresentQueuePolicy, PresentTarget, Queue, QueueRequest, QueueRole};
+pub use report::{DeviceReport, Unsuitability};
 pub use swapchain::{Swapchain, SwapchainBuilder};
+pub use validation::{LayerSettingValue, ValidationCheck, ValidationFeature};
 
 #[cfg(test)]
 mod tests {
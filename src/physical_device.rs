@@ -0,0 +1,216 @@
+use std::{borrow::Cow, ffi::CStr, ptr};
+
+use ash::vk;
+
+use crate::{Error, Features, Instance};
+
+/// A snapshot of a physical device's properties and capabilities.
+#[derive(Clone)]
+pub struct PhysicalDeviceInfo {
+    physical_device: vk::PhysicalDevice,
+    index: u32,
+    properties: vk::PhysicalDeviceProperties,
+    subgroup_properties: vk::PhysicalDeviceSubgroupProperties,
+    id_properties: vk::PhysicalDeviceIDProperties,
+    driver_properties: vk::PhysicalDeviceDriverProperties,
+    features: Features,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    queue_families: Vec<vk::QueueFamilyProperties>,
+    extensions: Vec<vk::ExtensionProperties>,
+}
+
+// The pNext pointers are cleared after querying, and the structs holding them
+// are only handed out as copies.
+unsafe impl Send for PhysicalDeviceInfo {}
+unsafe impl Sync for PhysicalDeviceInfo {}
+
+impl PhysicalDeviceInfo {
+    pub(crate) unsafe fn query(
+        instance: &Instance,
+        index: u32,
+        physical_device: vk::PhysicalDevice,
+    ) -> Result<Self, Error> {
+        let raw = instance.instance();
+        let properties = raw.get_physical_device_properties(physical_device);
+
+        // The per-version structs can only be queried from devices that know
+        // about them.
+        let device_version = properties.api_version.min(instance.api_version());
+        let extensions = raw.enumerate_device_extension_properties(physical_device)?;
+        let has_driver_properties = device_version >= vk::API_VERSION_1_2
+            || extensions.iter().any(|ext| {
+                CStr::from_ptr(ext.extension_name.as_ptr()) == vk::KhrDriverPropertiesFn::name()
+            });
+        let mut subgroup_properties = vk::PhysicalDeviceSubgroupProperties::default();
+        let mut id_properties = vk::PhysicalDeviceIDProperties::default();
+        let mut driver_properties = vk::PhysicalDeviceDriverProperties::default();
+        let mut properties2 = vk::PhysicalDeviceProperties2::builder();
+        if device_version >= vk::API_VERSION_1_1 {
+            properties2 = properties2
+                .push_next(&mut subgroup_properties)
+                .push_next(&mut id_properties);
+        }
+        if has_driver_properties {
+            properties2 = properties2.push_next(&mut driver_properties);
+        }
+        if !properties2.p_next.is_null() {
+            instance.get_physical_device_properties2(physical_device, &mut properties2);
+        }
+        subgroup_properties.p_next = ptr::null_mut();
+        id_properties.p_next = ptr::null_mut();
+        driver_properties.p_next = ptr::null_mut();
+
+        let mut features = Features::new();
+        let mut features2 = vk::PhysicalDeviceFeatures2::builder();
+        if device_version >= vk::API_VERSION_1_2 {
+            features2 = features2
+                .push_next(&mut features.vulkan11)
+                .push_next(&mut features.vulkan12);
+        }
+        if device_version >= vk::API_VERSION_1_3 {
+            features2 = features2.push_next(&mut features.vulkan13);
+        }
+        if !instance.get_physical_device_features2(physical_device, &mut features2) {
+            features2.features = raw.get_physical_device_features(physical_device);
+        }
+        features.vulkan10 = features2.features;
+        features.vulkan11.p_next = ptr::null_mut();
+        features.vulkan12.p_next = ptr::null_mut();
+        features.vulkan13.p_next = ptr::null_mut();
+
+        Ok(PhysicalDeviceInfo {
+            physical_device,
+            index,
+            properties,
+            subgroup_properties,
+            id_properties,
+            driver_properties,
+            features,
+            memory_properties: raw.get_physical_device_memory_properties(physical_device),
+            queue_families: raw.get_physical_device_queue_family_properties(physical_device),
+            extensions,
+        })
+    }
+
+    pub fn physical_device(&self) -> vk::PhysicalDevice {
+        self.physical_device
+    }
+
+    /// The device's position in `vkEnumeratePhysicalDevices`, as used by
+    /// [`PreferredDevice::Chosen`](crate::PreferredDevice::Chosen). It can
+    /// change between runs; see [`device_uuid`](Self::device_uuid).
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn name(&self) -> Cow<'_, str> {
+        unsafe { CStr::from_ptr(self.properties.device_name.as_ptr()) }.to_string_lossy()
+    }
+
+    pub fn device_type(&self) -> vk::PhysicalDeviceType {
+        self.properties.device_type
+    }
+
+    pub fn vendor_id(&self) -> u32 {
+        self.properties.vendor_id
+    }
+
+    pub fn properties(&self) -> &vk::PhysicalDeviceProperties {
+        &self.properties
+    }
+
+    pub fn limits(&self) -> &vk::PhysicalDeviceLimits {
+        &self.properties.limits
+    }
+
+    /// Zeroed unless the device supports Vulkan 1.1.
+    pub fn subgroup_properties(&self) -> vk::PhysicalDeviceSubgroupProperties {
+        self.subgroup_properties
+    }
+
+    /// Identifies the device across instances and runs, as used by
+    /// [`PreferredDevice::Uuid`](crate::PreferredDevice::Uuid). Zeroed unless
+    /// the device supports Vulkan 1.1.
+    pub fn device_uuid(&self) -> [u8; vk::UUID_SIZE] {
+        self.id_properties.device_uuid
+    }
+
+    /// Zeroed unless the device supports Vulkan 1.2 or `VK_KHR_driver_properties`.
+    pub fn driver_properties(&self) -> vk::PhysicalDeviceDriverProperties {
+        self.driver_properties
+    }
+
+    /// The driver's name, or an empty string without the driver properties.
+    pub fn driver_name(&self) -> Cow<'_, str> {
+        unsafe { CStr::from_ptr(self.driver_properties.driver_name.as_ptr()) }.to_string_lossy()
+    }
+
+    /// The driver's version string, or an empty string without the driver
+    /// properties.
+    pub fn driver_info(&self) -> Cow<'_, str> {
+        unsafe { CStr::from_ptr(self.driver_properties.driver_info.as_ptr()) }.to_string_lossy()
+    }
+
+    pub fn features(&self) -> &vk::PhysicalDeviceFeatures {
+        &self.features.vulkan10
+    }
+
+    /// Zeroed unless the device supports Vulkan 1.2.
+    pub fn vulkan11_features(&self) -> vk::PhysicalDeviceVulkan11Features {
+        self.features.vulkan11()
+    }
+
+    /// Zeroed unless the device supports Vulkan 1.2.
+    pub fn vulkan12_features(&self) -> vk::PhysicalDeviceVulkan12Features {
+        self.features.vulkan12()
+    }
+
+    /// Zeroed unless the device supports Vulkan 1.3.
+    pub fn vulkan13_features(&self) -> vk::PhysicalDeviceVulkan13Features {
+        self.features.vulkan13()
+    }
+
+    /// The features of every Vulkan version together.
+    pub fn all_features(&self) -> &Features {
+        &self.features
+    }
+
+    pub fn memory_properties(&self) -> &vk::PhysicalDeviceMemoryProperties {
+        &self.memory_properties
+    }
+
+    pub fn memory_heaps(&self) -> &[vk::MemoryHeap] {
+        &self.memory_properties.memory_heaps[..self.memory_properties.memory_heap_count as usize]
+    }
+
+    /// The total size of all device-local memory heaps.
+    pub fn device_local_memory(&self) -> vk::DeviceSize {
+        self.memory_heaps()
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum()
+    }
+
+    pub fn queue_families(&self) -> &[vk::QueueFamilyProperties] {
+        &self.queue_families
+    }
+
+    pub fn extensions(&self) -> &[vk::ExtensionProperties] {
+        &self.extensions
+    }
+
+    /// The spec version of a supported extension.
+    pub fn extension_version(&self, name: &CStr) -> Option<u32> {
+        self.extensions
+            .iter()
+            .find(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name)
+            .map(|ext| ext.spec_version)
+    }
+
+    pub fn supports_extension(&self, name: &CStr) -> bool {
+        self.extensions
+            .iter()
+            .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name)
+    }
+}
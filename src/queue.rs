@@ -0,0 +1,348 @@
+use std::{fmt, sync::Mutex};
+
+use ash::vk;
+
+use crate::Error;
+
+/// What a queue is for, used to look queues up with [`Device::queues`](crate::Device::queues).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QueueRole {
+    Graphics,
+    Compute,
+    Transfer,
+    Present,
+    /// Queues from [`QueueRequest`]s tagged with this handle.
+    Custom(u32),
+}
+
+/// Which family to present from when several can present to the surface.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PresentQueuePolicy {
+    /// The graphics family, so swapchain images needn't be shared between families.
+    #[default]
+    PreferGraphics,
+    /// A family other than the graphics one, to present without waiting on rendering.
+    PreferDedicated,
+    /// The compute family, for output rendered with compute shaders.
+    PreferCompute,
+}
+
+/// The display connection windows will be created on, to check presentation
+/// support before a surface exists. The instance needs the matching surface
+/// extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentTarget {
+    Win32,
+    Wayland {
+        display: *mut vk::wl_display,
+    },
+    Xcb {
+        connection: *mut vk::xcb_connection_t,
+        visual_id: vk::xcb_visualid_t,
+    },
+    Xlib {
+        display: *mut vk::Display,
+        visual_id: vk::VisualID,
+    },
+}
+
+/// Queues to create in addition to the default graphics, compute, transfer
+/// and present queues.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueueRequest {
+    pub(crate) role: QueueRole,
+    pub(crate) flags: vk::QueueFlags,
+    pub(crate) priorities: Vec<f32>,
+}
+
+impl QueueRequest {
+    /// One queue with priority 1.0 from a family supporting all of `flags`.
+    /// The family with the fewest other capabilities is picked.
+    pub fn new(role: QueueRole, flags: vk::QueueFlags) -> Self {
+        QueueRequest {
+            role,
+            flags,
+            priorities: vec![1.0],
+        }
+    }
+
+    /// Request `count` queues with priority 1.0.
+    pub fn count(mut self, count: usize) -> Self {
+        self.priorities = vec![1.0; count];
+        self
+    }
+
+    /// Request one queue per priority.
+    pub fn priorities(mut self, priorities: &[f32]) -> Self {
+        self.priorities = priorities.to_vec();
+        self
+    }
+
+    pub(crate) fn is_protected(&self) -> bool {
+        self.flags.contains(vk::QueueFlags::PROTECTED)
+    }
+
+    pub(crate) fn check_priorities(&self) -> Result<(), Error> {
+        match self
+            .priorities
+            .iter()
+            .find(|priority| !(0.0..=1.0).contains(*priority))
+        {
+            Some(&priority) => Err(Error::InvalidQueuePriority(priority)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A queue owned by a [`Device`](crate::Device). Roles that fall back to the
+/// same queue share one `Queue`, and its lock serializes access to the
+/// handle, so it can be used from several threads.
+pub struct Queue {
+    device: ash::Device,
+    queue: vk::Queue,
+    family_index: u32,
+    index: u32,
+    flags: vk::QueueFlags,
+    timestamp_valid_bits: u32,
+    lock: Mutex<()>,
+}
+
+impl Queue {
+    pub(crate) fn new(
+        device: ash::Device,
+        queue: vk::Queue,
+        family_index: u32,
+        index: u32,
+        family: &vk::QueueFamilyProperties,
+    ) -> Self {
+        Queue {
+            device,
+            queue,
+            family_index,
+            index,
+            flags: family.queue_flags,
+            timestamp_valid_bits: family.timestamp_valid_bits,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// The raw handle. Access to it must be externally synchronized, see
+    /// [`with_lock`](Self::with_lock).
+    pub fn handle(&self) -> vk::Queue {
+        self.queue
+    }
+
+    pub fn family_index(&self) -> u32 {
+        self.family_index
+    }
+
+    /// The queue's index within its family.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The capabilities of the queue's family.
+    pub fn flags(&self) -> vk::QueueFlags {
+        self.flags
+    }
+
+    /// The number of meaningful bits in timestamps written on this queue, or 0
+    /// if timestamps aren't supported.
+    pub fn timestamp_valid_bits(&self) -> u32 {
+        self.timestamp_valid_bits
+    }
+
+    /// Run `f` with the handle while holding the queue's lock, for commands
+    /// without a wrapper here.
+    pub fn with_lock<R>(&self, f: impl FnOnce(vk::Queue) -> R) -> R {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        f(self.queue)
+    }
+
+    /// # Safety
+    /// The submit infos must be valid for `vkQueueSubmit`.
+    pub unsafe fn submit(&self, submits: &[vk::SubmitInfo], fence: vk::Fence) -> Result<(), Error> {
+        self.with_lock(|queue| self.device.queue_submit(queue, submits, fence))?;
+        Ok(())
+    }
+
+    /// Returns whether the swapchain is suboptimal.
+    ///
+    /// # Safety
+    /// The present info must be valid for `vkQueuePresentKHR`.
+    pub unsafe fn present(
+        &self,
+        swapchain: &ash::extensions::khr::Swapchain,
+        present_info: &vk::PresentInfoKHR,
+    ) -> Result<bool, Error> {
+        Ok(self.with_lock(|queue| swapchain.queue_present(queue, present_info))?)
+    }
+
+    pub fn wait_idle(&self) -> Result<(), Error> {
+        self.with_lock(|queue| unsafe { self.device.queue_wait_idle(queue) })?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Queue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("queue", &self.queue)
+            .field("family_index", &self.family_index)
+            .field("index", &self.index)
+            .field("flags", &self.flags)
+            .finish()
+    }
+}
+
+/// Hands out queue indices within each family. Once a family runs out of
+/// queues, the ones already handed out are shared round-robin.
+pub(crate) struct QueueAllocator {
+    capacities: Vec<u32>,
+    // Per family, the priorities of the unprotected and the protected queues.
+    priorities: Vec<[Vec<f32>; 2]>,
+    aliased: Vec<[u32; 2]>,
+}
+
+impl QueueAllocator {
+    pub(crate) fn new(queue_families: &[vk::QueueFamilyProperties]) -> Self {
+        QueueAllocator {
+            capacities: queue_families.iter().map(|qf| qf.queue_count).collect(),
+            priorities: vec![Default::default(); queue_families.len()],
+            aliased: vec![[0; 2]; queue_families.len()],
+        }
+    }
+
+    /// The index of a queue in the family, or `None` if the family's queues
+    /// are all taken by queues of the other kind of protection.
+    pub(crate) fn allocate(&mut self, family: u32, protected: bool, priority: f32) -> Option<u32> {
+        let family = family as usize;
+        let kind = protected as usize;
+        let [unprotected, protected_queues] = &self.priorities[family];
+        let used = (unprotected.len() + protected_queues.len()) as u32;
+
+        let queues = &mut self.priorities[family][kind];
+        if used < self.capacities[family] {
+            queues.push(priority);
+            return Some(queues.len() as u32 - 1);
+        }
+        if queues.is_empty() {
+            return None;
+        }
+        let aliased = &mut self.aliased[family][kind];
+        let index = *aliased % queues.len() as u32;
+        *aliased += 1;
+        Some(index)
+    }
+
+    /// The create infos for every family with queues. They borrow from the allocator.
+    pub(crate) fn create_infos(&self) -> Vec<vk::DeviceQueueCreateInfo> {
+        let mut create_infos = Vec::new();
+        for (family, kinds) in self.priorities.iter().enumerate() {
+            for (kind, priorities) in kinds.iter().enumerate() {
+                if priorities.is_empty() {
+                    continue;
+                }
+                let flags = if kind == 1 {
+                    vk::DeviceQueueCreateFlags::PROTECTED
+                } else {
+                    vk::DeviceQueueCreateFlags::empty()
+                };
+                create_infos.push(
+                    vk::DeviceQueueCreateInfo::builder()
+                        .flags(flags)
+                        .queue_family_index(family as u32)
+                        .queue_priorities(priorities)
+                        .build(),
+                );
+            }
+        }
+        create_infos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family(queue_count: u32) -> vk::QueueFamilyProperties {
+        vk::QueueFamilyProperties {
+            queue_flags: vk::QueueFlags::GRAPHICS | vk::QueueFlags::PROTECTED,
+            queue_count,
+            ..Default::default()
+        }
+    }
+
+    fn priorities(create_info: &vk::DeviceQueueCreateInfo) -> &[f32] {
+        unsafe {
+            std::slice::from_raw_parts(
+                create_info.p_queue_priorities,
+                create_info.queue_count as usize,
+            )
+        }
+    }
+
+    #[test]
+    fn queues_and_their_device_can_be_shared() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Queue>();
+        assert_send_sync::<crate::Device>();
+        assert_send_sync::<crate::Instance>();
+    }
+
+    #[test]
+    fn allocator_shares_queues_once_full() {
+        let mut allocator = QueueAllocator::new(&[family(2), family(1)]);
+        assert_eq!(allocator.allocate(0, false, 1.0), Some(0));
+        assert_eq!(allocator.allocate(0, false, 0.5), Some(1));
+        assert_eq!(allocator.allocate(0, false, 0.5), Some(0));
+        assert_eq!(allocator.allocate(0, false, 0.5), Some(1));
+        assert_eq!(allocator.allocate(1, false, 0.25), Some(0));
+        assert_eq!(allocator.allocate(1, false, 0.25), Some(0));
+
+        let create_infos = allocator.create_infos();
+        assert_eq!(create_infos.len(), 2);
+        assert_eq!(create_infos[0].queue_family_index, 0);
+        assert_eq!(priorities(&create_infos[0]), [1.0, 0.5]);
+        assert_eq!(create_infos[1].queue_family_index, 1);
+        assert_eq!(priorities(&create_infos[1]), [0.25]);
+    }
+
+    #[test]
+    fn allocator_splits_protected_queues() {
+        let mut allocator = QueueAllocator::new(&[family(3)]);
+        assert_eq!(allocator.allocate(0, false, 1.0), Some(0));
+        assert_eq!(allocator.allocate(0, true, 0.5), Some(0));
+        assert_eq!(allocator.allocate(0, true, 0.5), Some(1));
+        // Full, so each kind shares its own queues.
+        assert_eq!(allocator.allocate(0, false, 1.0), Some(0));
+        assert_eq!(allocator.allocate(0, true, 1.0), Some(0));
+
+        let create_infos = allocator.create_infos();
+        assert_eq!(create_infos.len(), 2);
+        assert_eq!(create_infos[0].flags, vk::DeviceQueueCreateFlags::empty());
+        assert_eq!(priorities(&create_infos[0]), [1.0]);
+        assert_eq!(create_infos[1].flags, vk::DeviceQueueCreateFlags::PROTECTED);
+        assert_eq!(priorities(&create_infos[1]), [0.5, 0.5]);
+
+        // Protected queues can't alias unprotected ones.
+        let mut allocator = QueueAllocator::new(&[family(1)]);
+        assert_eq!(allocator.allocate(0, false, 1.0), Some(0));
+        assert_eq!(allocator.allocate(0, true, 1.0), None);
+    }
+
+    #[test]
+    fn queue_priorities_are_checked() {
+        let request = QueueRequest::new(QueueRole::Custom(0), vk::QueueFlags::COMPUTE);
+        assert!(request
+            .clone()
+            .priorities(&[0.0, 0.5, 1.0])
+            .check_priorities()
+            .is_ok());
+        assert!(matches!(
+            request.clone().priorities(&[0.5, 1.5]).check_priorities(),
+            Err(Error::InvalidQueuePriority(priority)) if priority == 1.5
+        ));
+        assert!(request.priorities(&[f32::NAN]).check_priorities().is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+
+use ash::vk;
+
+/// A reason a physical device can't be used with a [`DeviceBuilder`](crate::DeviceBuilder).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Unsuitability {
+    ApiVersionTooLow {
+        required: u32,
+        available: u32,
+    },
+    MissingFeatures(Vec<String>),
+    MissingExtension(String),
+    /// A device extension depends on an instance extension that isn't enabled.
+    /// `InstanceBuilder::enable_dependencies_of` enables it for `required_by`.
+    MissingInstanceExtension {
+        name: String,
+        required_by: String,
+    },
+    /// The descriptions of the [limits requirements](crate::DeviceBuilder::require_limits)
+    /// that weren't met.
+    LimitsNotMet(Vec<String>),
+    /// A [properties requirement](crate::DeviceBuilder::require_properties) wasn't met.
+    PropertiesNotMet,
+    FormatUnsupported {
+        format: vk::Format,
+        tiling: vk::ImageTiling,
+        features: vk::FormatFeatureFlags,
+    },
+    NotEnoughMemory {
+        required: vk::DeviceSize,
+        available: vk::DeviceSize,
+    },
+    /// No queue family supports a [`QueueRequest`](crate::QueueRequest)'s flags.
+    NoQueueFamily(vk::QueueFlags),
+    /// A [`QueueRequest`](crate::QueueRequest) asks for more queues than its
+    /// family has.
+    NotEnoughQueues {
+        flags: vk::QueueFlags,
+        requested: usize,
+        available: u32,
+    },
+    /// Every queue of a [`QueueRequest`](crate::QueueRequest)'s family is
+    /// taken by queues of the other protection.
+    QueuesTaken {
+        flags: vk::QueueFlags,
+        protected: bool,
+    },
+    /// No queue family can present to the [`PresentTarget`](crate::PresentTarget).
+    NoPresentTargetSupport,
+    NoGraphicsQueue,
+    NoComputeQueue,
+    NoPresentSupport(vk::SurfaceKHR),
+    RejectedByScorer,
+}
+
+impl fmt::Display for Unsuitability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unsuitability::ApiVersionTooLow {
+                required,
+                available,
+            } => write!(
+                f,
+                "API version {}.{} is lower than the required {}.{}",
+                vk::api_version_major(*available),
+                vk::api_version_minor(*available),
+                vk::api_version_major(*required),
+                vk::api_version_minor(*required),
+            ),
+            Unsuitability::MissingFeatures(features) => {
+                write!(f, "missing features: {}", features.join(", "))
+            }
+            Unsuitability::MissingExtension(name) => write!(f, "missing extension {}", name),
+            Unsuitability::MissingInstanceExtension { name, required_by } => write!(
+                f,
+                "instance extension {} is not enabled, which {} needs \
+                 (see InstanceBuilder::enable_dependencies_of)",
+                name, required_by
+            ),
+            Unsuitability::LimitsNotMet(limits) => {
+                write!(f, "limits not met: {}", limits.join(", "))
+            }
+            Unsuitability::PropertiesNotMet => write!(f, "properties don't meet the requirements"),
+            Unsuitability::FormatUnsupported {
+                format,
+                tiling,
+                features,
+            } => write!(
+                f,
+                "format {:?} doesn't support {:?} with {:?} tiling",
+                format, features, tiling
+            ),
+            Unsuitability::NotEnoughMemory {
+                required,
+                available,
+            } => write!(
+                f,
+                "{} MiB of device-local memory is less than the required {} MiB",
+                available >> 20,
+                required >> 20
+            ),
+            Unsuitability::NoQueueFamily(flags) => {
+                write!(f, "no queue family supports {:?}", flags)
+            }
+            Unsuitability::NotEnoughQueues {
+                flags,
+                requested,
+                available,
+            } => write!(
+                f,
+                "{} {:?} queues requested, but the family has {}",
+                requested, flags, available
+            ),
+            Unsuitability::QueuesTaken { flags, protected } => write!(
+                f,
+                "no {} {:?} queue left, the family's queues are all {}",
+                if *protected {
+                    "protected"
+                } else {
+                    "unprotected"
+                },
+                flags,
+                if *protected {
+                    "unprotected"
+                } else {
+                    "protected"
+                }
+            ),
+            Unsuitability::NoPresentTargetSupport => write!(f, "can't present to the display"),
+            Unsuitability::NoGraphicsQueue => write!(f, "no graphics queue"),
+            Unsuitability::NoComputeQueue => write!(f, "no compute queue"),
+            Unsuitability::NoPresentSupport(surface) => {
+                write!(f, "can't present to surface {:?}", surface)
+            }
+            Unsuitability::RejectedByScorer => write!(f, "rejected by the device scorer"),
+        }
+    }
+}
+
+/// How a physical device fared against a [`DeviceBuilder`](crate::DeviceBuilder)'s requirements.
+#[derive(Clone, Debug)]
+pub struct DeviceReport {
+    pub physical_device: vk::PhysicalDevice,
+    /// The device's position in `vkEnumeratePhysicalDevices`.
+    pub index: u32,
+    pub name: String,
+    /// Zeroed unless the device supports Vulkan 1.1.
+    pub device_uuid: [u8; vk::UUID_SIZE],
+    pub device_type: vk::PhysicalDeviceType,
+    /// The device scorer's result, or 0 without a scorer.
+    pub score: i64,
+    /// The total size of the device-local memory heaps.
+    pub device_local_memory: vk::DeviceSize,
+    pub failures: Vec<Unsuitability>,
+}
+
+impl DeviceReport {
+    pub fn is_suitable(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for DeviceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): ", self.name, self.index)?;
+        if self.failures.is_empty() {
+            return write!(f, "suitable");
+        }
+        for (i, failure) in self.failures.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", failure)?;
+        }
+        Ok(())
+    }
+}
@@ -124,9 +124,16 @@ impl SwapchainBuilder {
                 .present_mode(mode)
                 .surface(surface);
 
+            // Images are shared with the family that renders them.
+            let present_family = device
+                .present_queue_for(surface)?
+                .ok_or(Error::NoPresentQueue)?
+                .family_index();
             let queue_families = [
-                device.graphics_queue().unwrap().0,
-                device.present_queue().unwrap().0,
+                device
+                    .render_queue()
+                    .map_or(present_family, |queue| queue.family_index()),
+                present_family,
             ];
 
             let create_info = if queue_families[0] == queue_families[1] {
@@ -1,5 +1,7 @@
 use std::os::raw::c_char;
 
+use ash::vk;
+
 #[cfg(not(feature = "tracing"))]
 pub use log::{debug, error, info, trace, warn};
 
@@ -16,3 +18,23 @@ pub(crate) unsafe fn streq(mut a: *const c_char, mut b: *const c_char) -> bool {
     }
     false
 }
+
+/// Strip the patch number so versions compare by variant, major and minor only.
+pub(crate) fn without_patch(version: u32) -> u32 {
+    vk::make_api_version(
+        vk::api_version_variant(version),
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        0,
+    )
+}
+
+/// Format a version as `major.minor.patch`.
+pub(crate) fn version_string(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        vk::api_version_patch(version)
+    )
+}
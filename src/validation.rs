@@ -0,0 +1,283 @@
+use std::{
+    ffi::{c_void, CStr, CString},
+    os::raw::c_char,
+    ptr,
+};
+
+use ash::vk;
+
+/// Additional validation enabled through `VK_EXT_validation_features`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValidationFeature {
+    /// GPU-assisted validation of shader resource access.
+    GpuAssisted,
+    /// Reserve a descriptor set binding slot for GPU-assisted validation.
+    GpuAssistedReserveBindingSlot,
+    /// Warnings about API usage that is valid but likely to be slow.
+    BestPractices,
+    /// `debugPrintfEXT` support in shaders. Can't be combined with `GpuAssisted`.
+    DebugPrintf,
+    /// Detection of missing or incorrect synchronization.
+    Synchronization,
+}
+
+impl ValidationFeature {
+    pub(crate) fn to_vk(self) -> vk::ValidationFeatureEnableEXT {
+        match self {
+            ValidationFeature::GpuAssisted => vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+            ValidationFeature::GpuAssistedReserveBindingSlot => {
+                vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT
+            }
+            ValidationFeature::BestPractices => vk::ValidationFeatureEnableEXT::BEST_PRACTICES,
+            ValidationFeature::DebugPrintf => vk::ValidationFeatureEnableEXT::DEBUG_PRINTF,
+            ValidationFeature::Synchronization => {
+                vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION
+            }
+        }
+    }
+}
+
+/// Validation checks that can be turned off through `VK_EXT_validation_features`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValidationCheck {
+    All,
+    Shaders,
+    ThreadSafety,
+    ApiParameters,
+    ObjectLifetimes,
+    CoreChecks,
+    UniqueHandles,
+    ShaderValidationCache,
+}
+
+impl ValidationCheck {
+    pub(crate) fn to_vk(self) -> vk::ValidationFeatureDisableEXT {
+        match self {
+            ValidationCheck::All => vk::ValidationFeatureDisableEXT::ALL,
+            ValidationCheck::Shaders => vk::ValidationFeatureDisableEXT::SHADERS,
+            ValidationCheck::ThreadSafety => vk::ValidationFeatureDisableEXT::THREAD_SAFETY,
+            ValidationCheck::ApiParameters => vk::ValidationFeatureDisableEXT::API_PARAMETERS,
+            ValidationCheck::ObjectLifetimes => vk::ValidationFeatureDisableEXT::OBJECT_LIFETIMES,
+            ValidationCheck::CoreChecks => vk::ValidationFeatureDisableEXT::CORE_CHECKS,
+            ValidationCheck::UniqueHandles => vk::ValidationFeatureDisableEXT::UNIQUE_HANDLES,
+            ValidationCheck::ShaderValidationCache => {
+                vk::ValidationFeatureDisableEXT::SHADER_VALIDATION_CACHE
+            }
+        }
+    }
+}
+
+/// A value for [`InstanceBuilder::layer_setting`](crate::InstanceBuilder::layer_setting).
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayerSettingValue {
+    Bool(bool),
+    Int32(i32),
+    Int64(i64),
+    Uint32(u32),
+    Float(f32),
+    String(CString),
+    List(Vec<CString>),
+}
+
+impl From<bool> for LayerSettingValue {
+    fn from(value: bool) -> Self {
+        LayerSettingValue::Bool(value)
+    }
+}
+
+impl From<i32> for LayerSettingValue {
+    fn from(value: i32) -> Self {
+        LayerSettingValue::Int32(value)
+    }
+}
+
+impl From<i64> for LayerSettingValue {
+    fn from(value: i64) -> Self {
+        LayerSettingValue::Int64(value)
+    }
+}
+
+impl From<u32> for LayerSettingValue {
+    fn from(value: u32) -> Self {
+        LayerSettingValue::Uint32(value)
+    }
+}
+
+impl From<f32> for LayerSettingValue {
+    fn from(value: f32) -> Self {
+        LayerSettingValue::Float(value)
+    }
+}
+
+impl From<&CStr> for LayerSettingValue {
+    fn from(value: &CStr) -> Self {
+        LayerSettingValue::String(value.to_owned())
+    }
+}
+
+impl From<CString> for LayerSettingValue {
+    fn from(value: CString) -> Self {
+        LayerSettingValue::String(value)
+    }
+}
+
+impl From<&[&CStr]> for LayerSettingValue {
+    fn from(value: &[&CStr]) -> Self {
+        LayerSettingValue::List(value.iter().map(|s| (*s).to_owned()).collect())
+    }
+}
+
+impl From<Vec<CString>> for LayerSettingValue {
+    fn from(value: Vec<CString>) -> Self {
+        LayerSettingValue::List(value)
+    }
+}
+
+// VK_EXT_layer_settings is newer than the ash release we build against, so
+// the handful of definitions it needs are spelled out here.
+pub(crate) const LAYER_SETTINGS_EXTENSION_NAME: &CStr = c"VK_EXT_layer_settings";
+
+#[repr(C)]
+pub(crate) struct LayerSettingsCreateInfoEXT {
+    s_type: vk::StructureType,
+    p_next: *const c_void,
+    setting_count: u32,
+    p_settings: *const LayerSettingEXT,
+}
+
+unsafe impl vk::ExtendsInstanceCreateInfo for LayerSettingsCreateInfoEXT {}
+
+impl LayerSettingsCreateInfoEXT {
+    pub(crate) fn new(settings: &[LayerSettingEXT]) -> Self {
+        LayerSettingsCreateInfoEXT {
+            s_type: vk::StructureType::from_raw(1000496000),
+            p_next: ptr::null(),
+            setting_count: settings.len() as u32,
+            p_settings: settings.as_ptr(),
+        }
+    }
+}
+
+#[repr(C)]
+pub(crate) struct LayerSettingEXT {
+    p_layer_name: *const c_char,
+    p_setting_name: *const c_char,
+    ty: i32,
+    value_count: u32,
+    p_values: *const c_void,
+}
+
+enum RawSettingValues {
+    Bool32(Vec<vk::Bool32>),
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    Uint32(Vec<u32>),
+    Float32(Vec<f32>),
+    // The pointers borrow from the owned strings.
+    String {
+        _owned: Vec<CString>,
+        ptrs: Vec<*const c_char>,
+    },
+}
+
+/// A layer setting converted to the layout `VK_EXT_layer_settings` expects.
+pub(crate) struct LayerSetting {
+    layer: CString,
+    name: CString,
+    values: RawSettingValues,
+}
+
+impl LayerSetting {
+    pub(crate) fn new(layer: &CStr, name: &CStr, value: LayerSettingValue) -> Self {
+        let strings = |owned: Vec<CString>| {
+            let ptrs = owned.iter().map(|s| s.as_ptr()).collect();
+            RawSettingValues::String {
+                _owned: owned,
+                ptrs,
+            }
+        };
+
+        let values = match value {
+            LayerSettingValue::Bool(v) => RawSettingValues::Bool32(vec![v.into()]),
+            LayerSettingValue::Int32(v) => RawSettingValues::Int32(vec![v]),
+            LayerSettingValue::Int64(v) => RawSettingValues::Int64(vec![v]),
+            LayerSettingValue::Uint32(v) => RawSettingValues::Uint32(vec![v]),
+            LayerSettingValue::Float(v) => RawSettingValues::Float32(vec![v]),
+            LayerSettingValue::String(v) => strings(vec![v]),
+            LayerSettingValue::List(v) => strings(v),
+        };
+
+        LayerSetting {
+            layer: layer.to_owned(),
+            name: name.to_owned(),
+            values,
+        }
+    }
+
+    pub(crate) fn layer(&self) -> &CStr {
+        &self.layer
+    }
+
+    pub(crate) fn name(&self) -> &CStr {
+        &self.name
+    }
+
+    pub(crate) fn to_raw(&self) -> LayerSettingEXT {
+        fn raw<T>(values: &[T]) -> (u32, *const c_void) {
+            (values.len() as u32, values.as_ptr() as *const c_void)
+        }
+
+        // VkLayerSettingTypeEXT
+        let (ty, (value_count, p_values)) = match &self.values {
+            RawSettingValues::Bool32(v) => (0, raw(v)),
+            RawSettingValues::Int32(v) => (1, raw(v)),
+            RawSettingValues::Int64(v) => (2, raw(v)),
+            RawSettingValues::Uint32(v) => (3, raw(v)),
+            RawSettingValues::Float32(v) => (5, raw(v)),
+            RawSettingValues::String { ptrs, .. } => (7, raw(ptrs)),
+        };
+
+        LayerSettingEXT {
+            p_layer_name: self.layer.as_ptr(),
+            p_setting_name: self.name.as_ptr(),
+            ty,
+            value_count,
+            p_values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layer_setting_types() {
+        // VkLayerSettingTypeEXT values and value counts.
+        let cases: [(LayerSettingValue, i32, u32); 7] = [
+            (true.into(), 0, 1),
+            ((-1i32).into(), 1, 1),
+            ((-1i64).into(), 2, 1),
+            (3u32.into(), 3, 1),
+            (0.5f32.into(), 5, 1),
+            (c"value".into(), 7, 1),
+            ((&[c"a", c"b"][..]).into(), 7, 2),
+        ];
+        for (value, ty, count) in cases {
+            let setting = LayerSetting::new(c"VK_LAYER_test", c"setting", value);
+            let raw = setting.to_raw();
+            assert_eq!(raw.ty, ty);
+            assert_eq!(raw.value_count, count);
+            assert_eq!(
+                unsafe { CStr::from_ptr(raw.p_layer_name) },
+                c"VK_LAYER_test"
+            );
+            assert_eq!(unsafe { CStr::from_ptr(raw.p_setting_name) }, c"setting");
+        }
+
+        let setting = LayerSetting::new(c"VK_LAYER_test", c"list", vec![c"a".to_owned()].into());
+        let raw = setting.to_raw();
+        let values = unsafe { *(raw.p_values as *const *const c_char) };
+        assert_eq!(unsafe { CStr::from_ptr(values) }, c"a");
+    }
+}